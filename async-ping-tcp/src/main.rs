@@ -1,14 +1,31 @@
+use async_io::Async;
+use async_std::channel;
 use async_std::net::TcpStream;
 use async_std::prelude::*;
 use async_std::sync::{Arc, Mutex};
 use async_std::task;
-use clap::Parser;
-use std::collections::HashMap;
+use clap::{CommandFactory, Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::mem::ManuallyDrop;
 use std::net::SocketAddr;
-use std::sync::atomic::AtomicBool;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering::*;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::thread;
 use std::time::{Duration, Instant};
 const MAX_SAMPLES: usize = 100_000_000;
+// Sequence number of the messages asking the pong to switch to the size that
+// follows it
+const RESIZE: u64 = u64::MAX;
+// Framed messages start with their length as a little-endian u32
+const PREFIX: usize = 4;
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -19,69 +36,1544 @@ struct Args {
     wait: bool,
     #[clap(short, long, default_value = "0")]
     spawn: usize,
+    #[clap(long, value_enum, default_value = "sleep")]
+    load: Load,
+    #[clap(long, value_enum, default_value = "timer")]
+    pacing: Pacing,
+    #[clap(long)]
+    busy_poll: bool,
+    #[clap(long, default_value = "0")]
+    so_busy_poll: u32,
     #[clap(short, long)]
     csv: bool,
     #[clap(short, long, default_value = "60")]
     duration: u64,
+    #[clap(long, default_value = "1")]
+    clients: usize,
+    #[clap(long, default_value = "0")]
+    connections: usize,
+    #[clap(long, default_value = "0.01")]
+    active: f64,
+    #[clap(long)]
+    reconnect: bool,
+    #[clap(long)]
+    fast_open: bool,
+    #[clap(long, default_value = "0")]
+    depth: usize,
+    #[clap(long, value_parser = parse_positive)]
+    rate: Option<f64>,
+    #[clap(long, value_enum, default_value = "constant")]
+    arrival: Arrival,
+    #[clap(long, default_value = "0.1", value_parser = parse_positive)]
+    on: f64,
+    #[clap(long, default_value = "0.9", value_parser = parse_positive)]
+    off: f64,
+    #[clap(long)]
+    trace: Option<PathBuf>,
+    #[clap(long, default_value = "1.0", value_parser = parse_speed)]
+    speed: f64,
+    #[clap(long)]
+    sizes: Option<String>,
+    #[clap(long, default_value = "5", value_parser = parse_positive)]
+    step: f64,
+    #[clap(long, default_value = "100")]
+    warmup: usize,
+    #[clap(long)]
+    framed: bool,
+    #[clap(long, value_enum, default_value = "echo")]
+    mode: Mode,
+    #[clap(long)]
+    reply: Option<usize>,
+    #[clap(long, default_value = "0")]
+    noise: usize,
+    #[clap(long)]
+    noise_address: Option<SocketAddr>,
+    #[clap(long)]
+    noise_size: Option<usize>,
+    #[clap(long, value_parser = parse_positive)]
+    noise_rate: Option<f64>,
+    #[clap(long)]
+    noise_sink: bool,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: Option<usize>,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
 }
 
+// How many threads run the async-std executor, one per core when unset. It
+// goes in the framework column, plain async-std for the default.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    threads: Option<usize>,
+    pinning: Pinning,
+}
+
+impl RuntimeConfig {
+    // async-std reads it once, when its runtime starts on the first task
+    fn apply(&self) {
+        if let Some(n) = self.threads {
+            std::env::set_var("ASYNC_STD_THREAD_COUNT", n.to_string());
+        }
+        let pinning = self.pinning;
+        let mut watchers = Vec::new();
+        if pinning.cpus.is_some() {
+            // One per core when --threads is unset
+            let count = self
+                .threads
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+            watchers.push(pin_named("async-std/runtime", count, move |n| {
+                pinning.worker(n).unwrap()
+            }));
+        }
+        if let Some(core) = pinning.reactor {
+            watchers.push(pin_named("async-io", 1, move |_| core));
+        }
+        // Started first, for its threads not to inherit the core of this one,
+        // and on a timer for async-io to start its thread too
+        task::block_on(task::sleep(Duration::from_millis(1)));
+        // The test starts once they are all in place
+        for watcher in watchers {
+            watcher.join().unwrap();
+        }
+        pinning.pin_main();
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "async-std")?;
+        if let Some(n) = self.threads {
+            write!(f, "/threads={}", n)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// Background tasks competing with the test for the runtime: sleeping, spinning
+// without ever yielding, yielding in a tight loop, passing tokens around a ring
+// of channels, blocking the worker thread, or doing socket I/O of their own.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Load {
+    Sleep,
+    Spin,
+    Yield,
+    Channel,
+    Block,
+    Io,
+}
+
+impl Load {
+    fn name(&self) -> &'static str {
+        match self {
+            Load::Sleep => "sleep",
+            Load::Spin => "spin",
+            Load::Yield => "yield",
+            Load::Channel => "channel",
+            Load::Block => "block",
+            Load::Io => "io",
+        }
+    }
+}
+
+// The tasks column, the load profile and how many tasks run it
+#[derive(Clone, Copy, Debug)]
+struct Tasks {
+    load: Load,
+    count: usize,
+}
+
+impl fmt::Display for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.load.name(), self.count)
+    }
+}
+
+// How the socket waits for data: parked on the runtime's reactor or busy
+// polled, and the SO_BUSY_POLL budget in microseconds, 0 is off.
+#[derive(Clone, Copy, Debug)]
+struct Poll {
+    busy: bool,
+    budget: u32,
+}
+
+impl fmt::Display for Poll {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let wait = if self.busy { "busy" } else { "reactor" };
+        write!(f, "{}:{}", wait, self.budget)
+    }
+}
+
+// How the pings wait for their next send: the runtime's own timer, a blocking
+// sleep handed to another thread, spinning on the clock, or a timerfd armed with
+// the absolute deadline and read like any other file descriptor.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Pacing {
+    Timer,
+    Offload,
+    Spin,
+    Timerfd,
+}
+
+impl Pacing {
+    fn name(&self) -> &'static str {
+        match self {
+            Pacing::Timer => "timer",
+            Pacing::Offload => "offload",
+            Pacing::Spin => "spin",
+            Pacing::Timerfd => "timerfd",
+        }
+    }
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+fn timerfd(flags: libc::c_int) -> std::io::Result<OwnedFd> {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags | libc::TFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Arms the timerfd for an absolute deadline, Instant is CLOCK_MONOTONIC on Linux.
+// False if the deadline has passed already, a zero expiry would disarm it.
+fn arm(fd: RawFd, deadline: Instant) -> std::io::Result<bool> {
+    let wait = deadline.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        return Ok(false);
+    }
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let nanos = now.tv_nsec as u64 + wait.subsec_nanos() as u64;
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: now.tv_sec + (wait.as_secs() + nanos / 1_000_000_000) as libc::time_t,
+            tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+        },
+    };
+    let res =
+        unsafe { libc::timerfd_settime(fd, libc::TFD_TIMER_ABSTIME, &spec, std::ptr::null_mut()) };
+    if res < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+// How many times the timerfd expired since it was armed
+fn expirations(fd: RawFd) -> std::io::Result<u64> {
+    let mut count = [0u8; 8];
+    let n = unsafe { libc::read(fd, count.as_mut_ptr() as *mut libc::c_void, count.len()) };
+    if n < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(u64::from_ne_bytes(count))
+}
+
+// Waits with the chosen pacing, the timerfd is created once and armed again
+// for every deadline.
+struct Pacer {
+    pacing: Pacing,
+    timerfd: Option<Async<OwnedFd>>,
+}
+
+impl Pacer {
+    fn new(pacing: Pacing) -> std::io::Result<Pacer> {
+        let timerfd = match pacing {
+            Pacing::Timerfd => Some(Async::new(timerfd(libc::TFD_NONBLOCK)?)?),
+            _ => None,
+        };
+        Ok(Pacer { pacing, timerfd })
+    }
+
+    async fn sleep(&self, wait: Duration) {
+        self.until(Instant::now() + wait).await;
+    }
+
+    async fn until(&self, deadline: Instant) {
+        match self.pacing {
+            Pacing::Timer => task::sleep(deadline.saturating_duration_since(Instant::now())).await,
+            Pacing::Offload => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                task::spawn_blocking(move || std::thread::sleep(wait)).await;
+            }
+            Pacing::Spin => {
+                while Instant::now() < deadline {
+                    std::hint::spin_loop();
+                }
+            }
+            Pacing::Timerfd => {
+                let timerfd = self.timerfd.as_ref().unwrap();
+                if arm(timerfd.as_raw_fd(), deadline).unwrap() {
+                    timerfd
+                        .read_with(|fd| expirations(fd.as_raw_fd()))
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
+
+// SO_BUSY_POLL: blocking reads and epoll waits on the socket poll the device
+// queue for up to `micros` before going to sleep.
+fn set_busy_poll(socket: &impl AsRawFd, micros: u32) -> std::io::Result<()> {
+    let value = micros as libc::c_int;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BUSY_POLL,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// CPU time of the whole process, every thread included
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+// The std socket behind an async one, for non-blocking calls on it
+fn borrow_std<T: FromRawFd>(socket: &impl AsRawFd) -> ManuallyDrop<T> {
+    ManuallyDrop::new(unsafe { T::from_raw_fd(socket.as_raw_fd()) })
+}
+
+// Busy polling retries a non-blocking call until it goes through, yielding in
+// between so that the runtime never parks in epoll waiting for the socket.
+async fn spin<R>(mut op: impl FnMut() -> std::io::Result<R>) -> std::io::Result<R> {
+    loop {
+        match op() {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => task::yield_now().await,
+            res => return res,
+        }
+    }
+}
+
+async fn spin_read_exact(stream: &std::net::TcpStream, buf: &mut [u8]) -> std::io::Result<()> {
+    let mut read = 0;
+    while read < buf.len() {
+        let n = spin(|| (&*stream).read(&mut buf[read..])).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        read += n;
+    }
+    Ok(())
+}
+
+async fn spin_write_all(stream: &std::net::TcpStream, buf: &[u8]) -> std::io::Result<()> {
+    let mut written = 0;
+    while written < buf.len() {
+        written += spin(|| (&*stream).write(&buf[written..])).await?;
+    }
+    Ok(())
+}
+
+// The pong mode to expect, see the pong for what they do
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Mode {
+    Echo,
+    Fixed,
+    Sink,
+    Source,
+}
+
+impl Mode {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Echo => "echo",
+            Mode::Fixed => "fixed",
+            Mode::Sink => "sink",
+            Mode::Source => "source",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Arrival {
+    Constant,
+    Poisson,
+    Bursty,
+}
+
+impl Arrival {
+    fn name(&self) -> &'static str {
+        match self {
+            Arrival::Constant => "constant",
+            Arrival::Poisson => "poisson",
+            Arrival::Bursty => "bursty",
+        }
+    }
+}
+
+// Send deadlines as offsets from the start of the test. They are absolute, so
+// a late exchange does not push back the messages after it. Each send still
+// waits for the previous reply, once the round trip outgrows the gap the
+// achieved rate falls short of the target. Bursty arrivals send at the target
+// rate during the on periods and nothing during the off ones.
+struct Schedule {
+    arrival: Arrival,
+    gap: f64,
+    on: f64,
+    off: f64,
+    next: f64,
+    rng: StdRng,
+}
+
+impl Schedule {
+    fn new(arrival: Arrival, rate: f64, on: f64, off: f64) -> Schedule {
+        Schedule {
+            arrival,
+            gap: 1.0 / rate,
+            on,
+            off,
+            next: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        let at = self.next;
+        self.next += match self.arrival {
+            Arrival::Constant | Arrival::Bursty => self.gap,
+            // Exponential inter-arrival times
+            Arrival::Poisson => -(1.0 - self.rng.gen::<f64>()).ln() * self.gap,
+        };
+        if let Arrival::Bursty = self.arrival {
+            let period = self.on + self.off;
+            let phase = self.next % period;
+            if phase >= self.on {
+                self.next += period - phase;
+            }
+        }
+        Duration::from_secs_f64(at)
+    }
+}
+
+// Rates and times in seconds, which have to be positive
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok(x),
+        Ok(_) => Err(format!("{}: has to be positive", value)),
+        Err(e) => Err(format!("{:?}: {}", value, e)),
+    }
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+// Jain's fairness index: 1 when every client got the same share, 1/n when a
+// single one got everything.
+fn jain(shares: &[f64]) -> f64 {
+    let sum: f64 = shares.iter().sum();
+    let squares: f64 = shares.iter().map(|x| x * x).sum();
+    if squares == 0.0 {
+        return 0.0;
+    }
+    sum * sum / (shares.len() as f64 * squares)
+}
+
+// Sizes to sweep, either a list (64,1024,65536) or a geometric range
+// start:end[:factor] doubling by default.
+fn parse_sizes(sizes: &str) -> Result<Vec<usize>, String> {
+    let size = |size: &str| {
+        size.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("{:?}: {}", size, e))
+    };
+    match sizes.split_once(':') {
+        Some((start, rest)) => {
+            let (end, factor) = match rest.split_once(':') {
+                Some((end, factor)) => (size(end)?, size(factor)?),
+                None => (size(rest)?, 2),
+            };
+            let start = size(start)?;
+            if start == 0 {
+                return Err(format!("{}: a range has to start above 0", sizes));
+            }
+            if factor < 2 {
+                return Err(format!("{}: a range needs a factor of at least 2", sizes));
+            }
+            let mut range = Vec::new();
+            let mut next = Some(start);
+            while let Some(size) = next.filter(|size| *size <= end) {
+                range.push(size);
+                next = size.checked_mul(factor);
+            }
+            Ok(range)
+        }
+        None => sizes.split(',').map(size).collect(),
+    }
+}
+
+// Thousands of connections need as many file descriptors
+fn raise_fd_limit() -> std::io::Result<()> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    limit.rlim_cur = limit.rlim_max;
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// One message of a recorded trace
+struct Message {
+    offset: Duration,
+    size: usize,
+    connection: u64,
+}
+
+// Replays run faster or slower by a positive factor
+fn parse_speed(speed: &str) -> Result<f64, String> {
+    match speed.parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(factor),
+        Ok(_) => Err(format!("{}: the speed has to be a positive factor", speed)),
+        Err(e) => Err(format!("{:?}: {}", speed, e)),
+    }
+}
+
+// Traces ending in .csv hold one `offset,size[,connection]` line per message,
+// with the offset in seconds, lines that do not parse (headers) are skipped.
+// Any other trace is binary, 16 bytes per message: little-endian u64 offset in
+// ns, u32 size and u32 connection. Offsets are divided by speed.
+fn read_trace(path: &Path, speed: f64) -> std::io::Result<Vec<Message>> {
+    let data = fs::read(path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let mut trace = Vec::new();
+    if path.extension().is_some_and(|e| e == "csv") {
+        for (n, line) in String::from_utf8_lossy(&data).lines().enumerate() {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            let offset = fields[0].parse::<f64>();
+            let size = fields.get(1).map(|s| s.parse::<usize>());
+            if let (Ok(offset), Some(Ok(size))) = (offset, size) {
+                // Negative, NaN or overflowing offsets are no time to send at
+                let offset = Duration::try_from_secs_f64(offset / speed).map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{}:{}: offset {}: {}", path.display(), n + 1, fields[0], e),
+                    )
+                })?;
+                trace.push(Message {
+                    offset,
+                    size,
+                    connection: fields.get(2).and_then(|c| c.parse().ok()).unwrap_or(0),
+                });
+            }
+        }
+    } else {
+        for record in data.chunks_exact(16) {
+            let mut offset = [0u8; 8];
+            let mut size = [0u8; 4];
+            let mut connection = [0u8; 4];
+            offset.copy_from_slice(&record[0..8]);
+            size.copy_from_slice(&record[8..12]);
+            connection.copy_from_slice(&record[12..16]);
+            trace.push(Message {
+                offset: Duration::from_nanos(u64::from_le_bytes(offset)).div_f64(speed),
+                size: u32::from_le_bytes(size) as usize,
+                connection: u32::from_le_bytes(connection) as u64,
+            });
+        }
+    }
+    trace.sort_by_key(|m| m.offset);
+    Ok(trace)
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run_wait(
     address: SocketAddr,
     size: usize,
+    reply: usize,
     interval: f64,
     csv: bool,
-    tasks: usize,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    poll: Poll,
+    framed: bool,
+    mut schedule: Option<Schedule>,
     flag: Arc<AtomicBool>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
     let mut stream = TcpStream::connect(address).await?;
     stream.set_nodelay(true)?;
+    if poll.budget > 0 {
+        set_busy_poll(&stream, poll.budget)?;
+    }
+    let busy = poll
+        .busy
+        .then(|| borrow_std::<std::net::TcpStream>(&stream));
     let mut count: u64 = 0;
-    let mut payload = vec![0u8; size];
+    let header = if framed { PREFIX } else { 0 };
+    let mut payload = vec![0u8; header + size];
+    if framed {
+        payload[..PREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+    }
+    // Replies are as large as the requests unless the pong sends fixed ones
+    let mut response = vec![0u8; header + reply];
     let mut samples = Vec::with_capacity(MAX_SAMPLES);
+    // With a target rate the rate column holds the mean interval between sends
+    let interval = schedule.as_ref().map_or(interval, |s| s.gap);
+    let arrival = schedule.as_ref().map_or("interval", |s| s.arrival.name());
+    let cpu = cpu_time();
+    let start = Instant::now();
 
+    let pacer = Pacer::new(pacing)?;
     while flag.load(Relaxed) {
+        let deadline = match schedule.as_mut() {
+            Some(schedule) => {
+                let deadline = start + schedule.next();
+                pacer.until(deadline).await;
+                deadline
+            }
+            None => Instant::now(),
+        };
         let count_bytes: [u8; 8] = count.to_le_bytes();
-        payload[0..8].copy_from_slice(&count_bytes);
+        payload[header..header + 8].copy_from_slice(&count_bytes);
         let now = Instant::now();
 
-        stream.write_all(&payload).await.unwrap();
-        stream.read_exact(&mut payload).await.unwrap();
+        if let Some(socket) = busy.as_deref() {
+            spin_write_all(socket, &payload).await.unwrap();
+            spin_read_exact(socket, &mut response).await.unwrap();
+        } else {
+            stream.write_all(&payload).await.unwrap();
+            stream.read_exact(&mut response).await.unwrap();
+        }
 
-        let elapsed = now.elapsed();
+        let done = Instant::now();
+        let elapsed = done - now;
 
-        samples.push(elapsed);
+        // On a --rate schedule, a send that leaves after its deadline waited on
+        // the previous replies and the response time counts that wait too
+        // (coordinated omission). Interval pacing has no deadline to miss.
+        samples.push((count, elapsed, deadline - start, done - deadline));
 
-        task::sleep(Duration::from_secs_f64(interval)).await;
+        if schedule.is_none() {
+            pacer.sleep(Duration::from_secs_f64(interval)).await;
+        }
         count = count.wrapping_add(1);
     }
+    let achieved = count as f64 / start.elapsed().as_secs_f64();
+    let usage = (cpu_time() - cpu).as_secs_f64() / start.elapsed().as_secs_f64();
 
     stream.shutdown(async_std::net::Shutdown::Both).unwrap();
 
-    for s in samples {
+    for (count, s, intended, r) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, poll
+            println!(
+                "{},tcp,rtt,{},{},{},{},{},ns,{},{},{},{},{}",
+                runtime,
+                count,
+                interval,
+                size,
+                tasks,
+                s.as_nanos(),
+                family,
+                intended.as_nanos(),
+                arrival,
+                pacing.name(),
+                poll
+            );
+            // Interval pacing sends once the previous reply is back, the
+            // response time would only repeat the round trip
+            if schedule.is_some() {
+                println!(
+                    "{},tcp,response,{},{},{},{},{},ns,{},{},{},{},{}",
+                    runtime,
+                    count,
+                    interval,
+                    size,
+                    tasks,
+                    r.as_nanos(),
+                    family,
+                    intended.as_nanos(),
+                    arrival,
+                    pacing.name(),
+                    poll
+                );
+            }
+        } else {
+            println!(
+                "{} bytes: seq={} time={:?} response={:?}",
+                size, count, s, r
+            );
+        }
+    }
+
+    // Late replies hold back the sends after them, the achieved rate shows
+    // whether the loop kept up with the target
+    if schedule.is_some() {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, poll
+            println!(
+                "{},tcp,achieved,{},{},{},{},{:.1},msg/s,{},,{},{},{}",
+                runtime,
+                count,
+                interval,
+                size,
+                tasks,
+                achieved,
+                family,
+                arrival,
+                pacing.name(),
+                poll
+            );
+        } else {
+            println!("achieved={:.1} msg/s", achieved);
+        }
+    }
+
+    // Busy polling buys its latency with CPU time, all of the process counts
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, poll
+        println!(
+            "{},tcp,cpu,{},{},{},{},{:.1},%,{},,{},{},{}",
+            runtime,
+            count,
+            interval,
+            size,
+            tasks,
+            usage * 100.0,
+            family,
+            arrival,
+            pacing.name(),
+            poll
+        );
+    } else {
+        println!("cpu={:.1}%", usage * 100.0);
+    }
+    Ok(())
+}
+
+// Replays a trace, one connection per connection id of the trace. Messages leave at
+// their offset from the start of the replay and wait for their echo, one held
+// back by the previous reply shows up in the response time.
+#[allow(clippy::too_many_arguments)]
+async fn run_trace(
+    address: SocketAddr,
+    size: usize,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    framed: bool,
+    trace: Vec<Message>,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
+    // The rate column holds the mean interval between messages
+    let span = trace.last().map_or(0.0, |m| m.offset.as_secs_f64());
+    let interval = span / trace.len().max(1) as f64;
+    let mut connections = BTreeMap::<u64, Vec<Message>>::new();
+    for message in trace {
+        connections
+            .entry(message.connection)
+            .or_default()
+            .push(message);
+    }
+
+    let mut streams = Vec::with_capacity(connections.len());
+    for (connection, messages) in connections {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        streams.push((connection, stream, messages));
+    }
+
+    let start = Instant::now();
+    let handles: Vec<_> = streams
+        .into_iter()
+        .map(|(connection, mut stream, messages)| {
+            let c_flag = flag.clone();
+            task::spawn(async move {
+                // Unless framed, the pong echoes whole messages of `size` bytes
+                let header = if framed { PREFIX } else { 0 };
+                let bytes = |m: &Message| {
+                    if framed {
+                        m.size
+                    } else {
+                        m.size.max(1).div_ceil(size) * size
+                    }
+                };
+                let largest = messages.iter().map(bytes).max().unwrap_or(0);
+                let mut payload = vec![0u8; header + largest];
+                let mut samples = Vec::with_capacity(messages.len());
+                let pacer = Pacer::new(pacing).unwrap();
+                for (count, message) in messages.iter().enumerate() {
+                    if !c_flag.load(Relaxed) {
+                        break;
+                    }
+                    let deadline = start + message.offset;
+                    pacer.until(deadline).await;
+                    let bytes = bytes(message);
+                    if framed {
+                        payload[..PREFIX].copy_from_slice(&(bytes as u32).to_le_bytes());
+                    }
+                    let now = Instant::now();
+
+                    stream.write_all(&payload[..header + bytes]).await.unwrap();
+                    stream
+                        .read_exact(&mut payload[..header + bytes])
+                        .await
+                        .unwrap();
+
+                    let done = Instant::now();
+                    samples.push((
+                        connection,
+                        count,
+                        bytes,
+                        done - now,
+                        deadline - start,
+                        done - deadline,
+                    ));
+                }
+                samples
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        for (connection, count, bytes, s, intended, r) in handle.await {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, connection
+                println!(
+                    "{},tcp,rtt,{},{},{},{},{},ns,{},{},trace,{},{}",
+                    runtime,
+                    count,
+                    interval,
+                    bytes,
+                    tasks,
+                    s.as_nanos(),
+                    family,
+                    intended.as_nanos(),
+                    pacing.name(),
+                    connection
+                );
+                println!(
+                    "{},tcp,response,{},{},{},{},{},ns,{},{},trace,{},{}",
+                    runtime,
+                    count,
+                    interval,
+                    bytes,
+                    tasks,
+                    r.as_nanos(),
+                    family,
+                    intended.as_nanos(),
+                    pacing.name(),
+                    connection
+                );
+            } else {
+                println!(
+                    "{} bytes: connection={} seq={} time={:?} response={:?}",
+                    bytes, connection, count, s, r
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Steps through the sizes on a single connection. Each step asks the pong to
+// switch size, then discards `warmup` exchanges before recording for `step`.
+#[allow(clippy::too_many_arguments)]
+async fn run_sweep(
+    address: SocketAddr,
+    size: usize,
+    sizes: Vec<usize>,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    framed: bool,
+    warmup: usize,
+    step: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
+    // Resize requests carry a sequence number and the new size, framed
+    // messages only the sequence number
+    let least = if framed { 8 } else { 16 };
+    if size < least || sizes.iter().any(|s| *s < least) {
+        return Err(format!("sweeping needs sizes of at least {} bytes", least).into());
+    }
+    let mut stream = TcpStream::connect(address).await?;
+    stream.set_nodelay(true)?;
+    let header = if framed { PREFIX } else { 0 };
+    let mut payload = vec![0u8; header + size];
+    let mut samples = Vec::with_capacity(MAX_SAMPLES);
+    let pacer = Pacer::new(pacing)?;
+    let start = Instant::now();
+
+    for size in sizes {
+        if framed {
+            // Framed messages carry their size, the pong needs no warning
+            payload.resize(PREFIX + size, 0);
+            payload[..PREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+        } else {
+            payload[0..8].copy_from_slice(&RESIZE.to_le_bytes());
+            payload[8..16].copy_from_slice(&(size as u64).to_le_bytes());
+            stream.write_all(&payload).await?;
+            stream.read_exact(&mut payload).await?;
+            payload.resize(size, 0);
+        }
+
+        let mut count: u64 = 0;
+        for _ in 0..warmup {
+            payload[header..header + 8].copy_from_slice(&count.to_le_bytes());
+            stream.write_all(&payload).await?;
+            stream.read_exact(&mut payload).await?;
+        }
+
+        let end = Instant::now() + step;
+        while Instant::now() < end {
+            payload[header..header + 8].copy_from_slice(&count.to_le_bytes());
+            let now = Instant::now();
+
+            stream.write_all(&payload).await?;
+            stream.read_exact(&mut payload).await?;
+            samples.push((count, size, now.elapsed(), now - start));
+
+            pacer.sleep(Duration::from_secs_f64(interval)).await;
+            count = count.wrapping_add(1);
+        }
+    }
+
+    for (count, size, s, intended) in samples {
         if csv {
-            // framework, transport, test, count, rate, payload, tasks, value, unit
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing
             println!(
-                "async-std,tcp,rtt,{},{},{},{},{},ns",
+                "{},tcp,rtt,{},{},{},{},{},ns,{},{},interval,{}",
+                runtime,
                 count,
                 interval,
-                payload.len(),
+                size,
+                tasks,
+                s.as_nanos(),
+                family,
+                intended.as_nanos(),
+                pacing.name()
+            );
+        } else {
+            println!("{} bytes: seq={} time={:?}", size, count, s);
+        }
+    }
+    Ok(())
+}
+
+// Sink and source modes: the ping writes requests back to back, or sends a
+// single one and reads the replies streaming back, and reports how many
+// messages per second got through.
+#[allow(clippy::too_many_arguments)]
+async fn run_stream(
+    address: SocketAddr,
+    size: usize,
+    reply: usize,
+    mode: Mode,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    framed: bool,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
+    let mut stream = TcpStream::connect(address).await?;
+    stream.set_nodelay(true)?;
+    let header = if framed { PREFIX } else { 0 };
+    let mut payload = vec![0u8; header + size];
+    if framed {
+        payload[..PREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+    }
+    let mut response = vec![0u8; header + reply];
+    let mut counts: Vec<u64> = Vec::new();
+    let start = Instant::now();
+
+    if let Mode::Source = mode {
+        stream.write_all(&payload).await?;
+    }
+    while flag.load(Relaxed) {
+        if let Mode::Source = mode {
+            stream.read_exact(&mut response).await?;
+        } else {
+            stream.write_all(&payload).await?;
+        }
+        // Seconds are counted from the start, those without a message stay at 0
+        let second = start.elapsed().as_secs() as usize;
+        if counts.len() <= second {
+            counts.resize(second + 1, 0);
+        }
+        counts[second] += 1;
+    }
+
+    // The test ends partway through the last second
+    counts.truncate(start.elapsed().as_secs() as usize);
+
+    let bytes = if let Mode::Source = mode { reply } else { size };
+    for (second, count) in counts.into_iter().enumerate() {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, mode
+            println!(
+                "{},tcp,throughput,{},{},{},{},{},msg/s,{},{}",
+                runtime,
+                second,
+                interval,
+                bytes,
+                tasks,
+                count,
+                family,
+                mode.name()
+            );
+        } else {
+            println!("{} bytes: second={} {} msg/s", bytes, second, count);
+        }
+    }
+    Ok(())
+}
+
+// With TCP Fast Open the handshake is deferred to the first write, whose data
+// rides on the SYN. On loopback it needs net.ipv4.tcp_fastopen=3.
+async fn connect(address: SocketAddr, fast_open: bool) -> std::io::Result<TcpStream> {
+    let stream = if fast_open {
+        let socket = Socket::new(
+            Domain::for_address(address),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        let enable: libc::c_int = 1;
+        let res = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_FASTOPEN_CONNECT,
+                &enable as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if res != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Connected in the background like TcpStream::connect, right away
+        // when the handshake is deferred to the first write
+        socket.set_nonblocking(true)?;
+        match socket.connect(&address.into()) {
+            Err(e) if e.raw_os_error() != Some(libc::EINPROGRESS) => return Err(e),
+            _ => (),
+        }
+        let stream = Async::new(std::net::TcpStream::from(socket))?;
+        stream.writable().await?;
+        if let Some(e) = stream.get_ref().take_error()? {
+            return Err(e);
+        }
+        TcpStream::from(stream.into_inner()?)
+    } else {
+        TcpStream::connect(address).await?
+    };
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+// A new connection for every request: connect, one exchange, close. Each of
+// the clients opens its next connection once the last one closed.
+#[allow(clippy::too_many_arguments)]
+async fn run_reconnect(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    fast_open: bool,
+    clients: usize,
+    framed: bool,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
+    let header = if framed { PREFIX } else { 0 };
+    let transport = if fast_open { "tcp-tfo" } else { "tcp" };
+    let start = Instant::now();
+
+    let handles: Vec<_> = (0..clients)
+        .map(|_| {
+            let c_flag = flag.clone();
+            task::spawn(async move {
+                let mut count: u64 = 0;
+                let mut payload = vec![0u8; header + size];
+                if framed {
+                    payload[..PREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+                }
+                let mut samples = Vec::with_capacity(MAX_SAMPLES / clients);
+                let pacer = Pacer::new(pacing).unwrap();
+                while c_flag.load(Relaxed) {
+                    let count_bytes: [u8; 8] = count.to_le_bytes();
+                    payload[header..header + 8].copy_from_slice(&count_bytes);
+                    let now = Instant::now();
+
+                    let mut stream = connect(address, fast_open).await.unwrap();
+                    let connected = now.elapsed();
+                    stream.write_all(&payload).await.unwrap();
+                    stream.read_exact(&mut payload[..1]).await.unwrap();
+                    let first_byte = now.elapsed();
+                    stream.read_exact(&mut payload[1..]).await.unwrap();
+                    let elapsed = now.elapsed();
+                    drop(stream);
+                    samples.push((count, connected, first_byte, elapsed));
+
+                    pacer.sleep(Duration::from_secs_f64(interval)).await;
+                    count = count.wrapping_add(1);
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(clients);
+    for handle in handles {
+        results.push(handle.await);
+    }
+    let elapsed = start.elapsed();
+    let accepted: usize = results.iter().map(|samples| samples.len()).sum();
+
+    for (client, samples) in results.into_iter().enumerate() {
+        for (c, connected, first_byte, elapsed) in samples {
+            for (test, s) in [
+                ("connect", connected),
+                ("first-byte", first_byte),
+                ("rtt", elapsed),
+            ] {
+                if csv {
+                    // framework, transport, test, count, rate, payload, tasks, value, unit, family, pacing, client
+                    println!(
+                        "{},{},{},{},{},{},{},{},ns,{},{},{}",
+                        runtime,
+                        transport,
+                        test,
+                        c,
+                        interval,
+                        size,
+                        tasks,
+                        s.as_nanos(),
+                        family,
+                        pacing.name(),
+                        client
+                    );
+                } else {
+                    println!(
+                        "{} bytes: client={} seq={} {}={:?}",
+                        size, client, c, test, s
+                    );
+                }
+            }
+        }
+    }
+
+    // Only clients connecting back to back, enough of them to cover their
+    // round trips, load the listener up to the accept rate it sustains
+    if interval == 0.0 {
+        let rate = accepted as f64 / elapsed.as_secs_f64();
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, pacing, client
+            println!(
+                "{},{},accept-rate,{},{},{},{},{},conn/s,{},{},all",
+                runtime,
+                transport,
+                accepted,
+                interval,
+                size,
+                tasks,
+                rate,
+                family,
+                pacing.name()
+            );
+        } else {
+            println!("accept rate: {:.1} conn/s over {} clients", rate, clients);
+        }
+    }
+    Ok(())
+}
+
+// Every client connects before any of them starts, so that they all compete
+// for the pong over the whole test. The idle connections are only opened, so
+// that the pong has to juggle them alongside the active ones.
+#[allow(clippy::too_many_arguments)]
+async fn run_fan_in(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    clients: usize,
+    idle: usize,
+    framed: bool,
+    duration: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
+    let header = if framed { PREFIX } else { 0 };
+    let mut parked = Vec::with_capacity(idle);
+    for _ in 0..idle {
+        parked.push(TcpStream::connect(address).await?);
+    }
+
+    let mut streams = Vec::with_capacity(clients);
+    for _ in 0..clients {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        streams.push(stream);
+    }
+
+    // Opening thousands of connections takes a while, the test starts once
+    // they are all up.
+    let flag = Arc::new(AtomicBool::new(true));
+    let c_flag = flag.clone();
+    task::spawn(async move {
+        task::sleep(duration).await;
+        c_flag.store(false, Relaxed);
+    });
+
+    let handles: Vec<_> = streams
+        .into_iter()
+        .map(|mut stream| {
+            let c_flag = flag.clone();
+            task::spawn(async move {
+                let mut count: u64 = 0;
+                let mut payload = vec![0u8; header + size];
+                if framed {
+                    payload[..PREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+                }
+                let mut samples = Vec::with_capacity(MAX_SAMPLES / clients);
+                let pacer = Pacer::new(pacing).unwrap();
+                while c_flag.load(Relaxed) {
+                    let count_bytes: [u8; 8] = count.to_le_bytes();
+                    payload[header..header + 8].copy_from_slice(&count_bytes);
+                    let now = Instant::now();
+
+                    stream.write_all(&payload).await.unwrap();
+                    stream.read_exact(&mut payload).await.unwrap();
+                    samples.push((count, now.elapsed()));
+
+                    pacer.sleep(Duration::from_secs_f64(interval)).await;
+                    count = count.wrapping_add(1);
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut shares = Vec::with_capacity(clients);
+    for (client, handle) in handles.into_iter().enumerate() {
+        let samples = handle.await;
+        shares.push(samples.len() as f64);
+        for (c, s) in samples {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, client, connections, pacing
+                println!(
+                    "{},tcp,rtt,{},{},{},{},{},ns,{},{},{},{}",
+                    runtime,
+                    c,
+                    interval,
+                    size,
+                    tasks,
+                    s.as_nanos(),
+                    family,
+                    client,
+                    clients + idle,
+                    pacing.name()
+                );
+            } else {
+                println!("{} bytes: client={} seq={} time={:?}", size, client, c, s);
+            }
+        }
+    }
+
+    // How evenly the pong shared its round trips among the clients
+    let fairness = jain(&shares);
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, client, connections, pacing
+        println!(
+            "{},tcp,fairness,{},{},{},{},{},jain,{},all,{},{}",
+            runtime,
+            clients,
+            interval,
+            size,
+            tasks,
+            fairness,
+            family,
+            clients + idle,
+            pacing.name()
+        );
+    } else {
+        println!("fairness: jain={:.4}", fairness);
+    }
+    Ok(())
+}
+
+// Keeps `depth` requests in flight: a new one leaves as soon as a reply comes
+// back, so the pipeline paces itself and the interval is not used.
+#[allow(clippy::too_many_arguments)]
+async fn run_depth(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    depth: usize,
+    framed: bool,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
+    let header = if framed { PREFIX } else { 0 };
+    let stream = TcpStream::connect(address).await?;
+    stream.set_nodelay(true)?;
+    let mut read_stream = stream.clone();
+    let mut write_stream = stream;
+    // Requests in flight, in the order the pong echoes them, and one credit
+    // per free slot of the pipeline
+    let (in_flight, echoes) = channel::unbounded::<(u64, Instant)>();
+    let (credit, credits) = channel::bounded::<()>(depth);
+    for _ in 0..depth {
+        credit.try_send(()).unwrap();
+    }
+
+    // Replies are read while the requests go out: once the pipeline outgrows
+    // the socket buffers, both sides would otherwise block on their writes
+    let reader = task::spawn(async move {
+        let mut payload = vec![0u8; header + size];
+        let mut count_bytes = [0u8; 8];
+        let mut samples = Vec::with_capacity(MAX_SAMPLES);
+        let mut lost: u64 = 0;
+        while let Ok((c, instant)) = echoes.recv().await {
+            read_stream.read_exact(&mut payload).await.unwrap();
+            count_bytes.copy_from_slice(&payload[header..header + 8]);
+            if u64::from_le_bytes(count_bytes) == c {
+                samples.push((c, instant.elapsed()));
+            } else {
+                // The stream is ordered, an echo out of sequence means
+                // replies went missing
+                lost += 1;
+            }
+            credit.try_send(()).unwrap();
+        }
+        (samples, lost)
+    });
+
+    // Top the pipeline up until the test is over, the reader drains it
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; header + size];
+    if framed {
+        payload[..PREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+    }
+    while flag.load(Relaxed) {
+        credits.recv().await.unwrap();
+        payload[header..header + 8].copy_from_slice(&count.to_le_bytes());
+        in_flight.try_send((count, Instant::now())).unwrap();
+        write_stream.write_all(&payload).await.unwrap();
+        count = count.wrapping_add(1);
+    }
+    drop(in_flight);
+    let (samples, lost) = reader.await;
+
+    for (c, s) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+            println!(
+                "{},tcp,rtt,{},{},{},{},{},ns,{},{}",
+                runtime,
+                c,
+                interval,
+                size,
                 tasks,
-                s.as_nanos()
+                s.as_nanos(),
+                family,
+                depth
             );
         } else {
-            println!("{} bytes: seq={} time={:?}", payload.len(), count, s);
+            println!("{} bytes: seq={} depth={} time={:?}", size, c, depth, s);
         }
     }
+
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+        println!(
+            "{},tcp,lost,{},{},{},{},{},msgs,{},{}",
+            runtime, count, interval, size, tasks, lost, family, depth
+        );
+    } else {
+        println!("lost={}", lost);
+    }
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run(
     address: SocketAddr,
     size: usize,
     interval: f64,
     csv: bool,
-    tasks: usize,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    framed: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(address);
+    let header = if framed { PREFIX } else { 0 };
     let mut stream = TcpStream::connect(address).await?;
     stream.set_nodelay(true)?;
     let pending = Arc::new(Mutex::new(HashMap::<u64, Instant>::new()));
@@ -89,87 +1581,502 @@ async fn run(
     let mut c_stream = stream.clone();
     let c_pending = pending.clone();
     task::spawn(async move {
-        let mut payload = vec![0u8; size];
+        let mut payload = vec![0u8; header + size];
         let mut count_bytes = [0u8; 8];
         loop {
             c_stream.read_exact(&mut payload).await.unwrap();
-            count_bytes.copy_from_slice(&payload[0..8]);
+            count_bytes.copy_from_slice(&payload[header..header + 8]);
             let count = u64::from_le_bytes(count_bytes);
 
             let instant = c_pending.lock().await.remove(&count).unwrap();
             if csv {
-                // framework, transport, test, count, rate, payload, tasks, value, unit
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, pacing
                 println!(
-                    "async-std,tcp,rtt,{},{},{},{},{},ns",
+                    "{},tcp,rtt,{},{},{},{},{},ns,{},{}",
+                    runtime,
                     count,
                     interval,
-                    payload.len(),
+                    size,
                     tasks,
-                    instant.elapsed().as_nanos()
+                    instant.elapsed().as_nanos(),
+                    family,
+                    pacing.name()
                 );
             } else {
-                println!(
-                    "{} bytes: seq={} time={:?}",
-                    payload.len(),
-                    count,
-                    instant.elapsed()
-                );
+                println!("{} bytes: seq={} time={:?}", size, count, instant.elapsed());
             }
         }
     });
 
     //Perform RUN tests
     let mut count: u64 = 0;
+    let pacer = Pacer::new(pacing)?;
     loop {
-        let mut payload = vec![0u8; size];
+        let mut payload = vec![0u8; header + size];
+        if framed {
+            payload[..PREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+        }
         let count_bytes: [u8; 8] = count.to_le_bytes();
-        payload[0..8].copy_from_slice(&count_bytes);
+        payload[header..header + 8].copy_from_slice(&count_bytes);
 
         pending.lock().await.insert(count, Instant::now());
         stream.write_all(&payload).await.unwrap();
 
-        task::sleep(Duration::from_secs_f64(interval)).await;
+        pacer.sleep(Duration::from_secs_f64(interval)).await;
         count = count.wrapping_add(1);
     }
 }
 
+async fn background(load: Load) {
+    let mut x: usize = 1;
+    match load {
+        Load::Sleep => loop {
+            x = x.wrapping_mul(2);
+            task::sleep(Duration::from_millis(1)).await;
+        },
+        // Never yields, so it keeps a worker for itself
+        Load::Spin => loop {
+            x = std::hint::black_box(x.wrapping_mul(2));
+        },
+        Load::Yield => loop {
+            task::yield_now().await;
+        },
+        Load::Block => loop {
+            std::thread::sleep(Duration::from_millis(1));
+            task::yield_now().await;
+        },
+        Load::Io => {
+            let socket = async_std::net::UdpSocket::bind("127.0.0.1:0")
+                .await
+                .unwrap();
+            socket.connect(socket.local_addr().unwrap()).await.unwrap();
+            let mut buf = [0u8; 64];
+            loop {
+                socket.send(&buf).await.unwrap();
+                socket.recv(&mut buf).await.unwrap();
+                // The datagram is always there already, which is no reason
+                // to keep the worker
+                task::yield_now().await;
+            }
+        }
+        Load::Channel => unreachable!("Channel tasks are spawned as a ring"),
+    }
+}
+
+fn spawn_load(load: Load, count: usize) {
+    if let Load::Channel = load {
+        // Every task forwards the tokens it gets to the next one
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..count)
+            .map(|_| async_std::channel::bounded::<u64>(1))
+            .unzip();
+        for tx in &txs {
+            tx.try_send(0).unwrap();
+        }
+        for (i, rx) in rxs.into_iter().enumerate() {
+            let tx = txs[(i + 1) % count].clone();
+            task::spawn(async move {
+                while let Ok(token) = rx.recv().await {
+                    if tx.send(token).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        return;
+    }
+    for _ in 0..count {
+        task::spawn(background(load));
+    }
+}
+
+// Connections loading the runtime next to the measured one. They send `size`
+// bytes messages `gap` apart, or back to back, and read the echoes unless the
+// other end is a sink.
+#[derive(Clone, Copy, Debug)]
+struct Noise {
+    address: SocketAddr,
+    connections: usize,
+    size: usize,
+    gap: Option<Duration>,
+    sink: bool,
+    framed: bool,
+}
+
+impl Noise {
+    // Returns the count of messages sent so far
+    fn spawn(&self) -> Arc<AtomicU64> {
+        let sent = Arc::new(AtomicU64::new(0));
+        for _ in 0..self.connections {
+            let noise = *self;
+            let sent = sent.clone();
+            task::spawn(async move {
+                // A noise connection going away shows in the achieved rate
+                let _ = noise.run(sent).await;
+            });
+        }
+        sent
+    }
+
+    async fn run(&self, sent: Arc<AtomicU64>) -> std::io::Result<()> {
+        let stream = TcpStream::connect(self.address).await?;
+        stream.set_nodelay(true)?;
+        let (mut read_stream, mut write_stream) = (stream.clone(), stream);
+        let header = if self.framed { PREFIX } else { 0 };
+        let mut payload = vec![0u8; header + self.size];
+        if self.framed {
+            payload[..PREFIX].copy_from_slice(&(self.size as u32).to_le_bytes());
+        }
+        // Echoes are read while the message goes out: one larger than the
+        // socket buffers would otherwise leave both sides blocked on their writes
+        let (echo, echoes) = channel::bounded::<()>(1);
+        if !self.sink {
+            let mut reply = vec![0u8; payload.len()];
+            task::spawn(async move {
+                while read_stream.read_exact(&mut reply).await.is_ok()
+                    && echo.send(()).await.is_ok()
+                {}
+            });
+        }
+        let start = Instant::now();
+        let mut next = Duration::ZERO;
+
+        loop {
+            write_stream.write_all(&payload).await?;
+            if !self.sink && echoes.recv().await.is_err() {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            sent.fetch_add(1, Relaxed);
+            if let Some(gap) = self.gap {
+                next += gap;
+                if let Some(wait) = (start + next).checked_duration_since(Instant::now()) {
+                    task::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    fn report(
+        &self,
+        sent: u64,
+        elapsed: Duration,
+        csv: bool,
+        tasks: Tasks,
+        runtime: RuntimeConfig,
+    ) {
+        let interval = self.gap.map_or(0.0, |gap| gap.as_secs_f64());
+        let rate = (sent as f64 / elapsed.as_secs_f64()) as u64;
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, connections
+            println!(
+                "{},tcp,noise,{},{},{},{},{},msg/s,{},{}",
+                runtime,
+                sent,
+                interval,
+                self.size,
+                tasks,
+                rate,
+                family(self.address),
+                self.connections
+            );
+        } else {
+            println!(
+                "noise: connections={} sent={} {} msg/s",
+                self.connections, sent, rate
+            );
+        }
+    }
+}
+
+// Out of all the connections only a fraction is active, as (active, idle)
+fn connections(args: &Args) -> (usize, usize) {
+    match args.connections {
+        0 => (args.clients, 0),
+        n => {
+            let active = ((n as f64 * args.active).round() as usize).clamp(1, n);
+            (active, n - active)
+        }
+    }
+}
+
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    let (clients, idle) = connections(args);
+    // The request/reply loop runs on a single connection, without the
+    // loops of --trace, --sizes, --reconnect or --depth
+    let single = args.trace.is_none()
+        && args.sizes.is_none()
+        && !args.reconnect
+        && args.depth == 0
+        && clients == 1
+        && idle == 0;
+    let request_reply = single && matches!(args.mode, Mode::Echo | Mode::Fixed);
+    // Only the request/reply loop reads fixed replies, sink and source have their own
+    if !matches!(args.mode, Mode::Echo) && !single {
+        return Err(conflict(
+            "Modes other than echo need a single connection, and no --trace, --sizes, --reconnect or --depth",
+        ));
+    }
+    // A connection per request leaves none of them open
+    if args.reconnect && idle > 0 {
+        return Err(conflict(
+            "--reconnect opens --clients connections at a time, --connections is for long lived ones",
+        ));
+    }
+    // Noise connections expect echoes or a sink at the other end
+    if args.noise > 0
+        && !matches!(args.mode, Mode::Echo)
+        && !args.noise_sink
+        && args.noise_address.is_none()
+    {
+        return Err(conflict(
+            "Noise connections to a non-echo pong need --noise-sink or --noise-address",
+        ));
+    }
+    // An unframed echo pong answers in --size chunks
+    if args.noise > 0
+        && !args.framed
+        && !args.noise_sink
+        && args.noise_size.unwrap_or(args.size) != args.size
+    {
+        return Err(conflict(
+            "A --noise-size other than the size needs --framed or --noise-sink",
+        ));
+    }
+    // Only the request/reply loop follows a target rate, and busy polls
+    if args.rate.is_some() && !request_reply {
+        return Err(conflict(
+            "--rate needs a single connection, and no --trace, --sizes, --reconnect, --depth or sink and source modes",
+        ));
+    }
+    if (args.busy_poll || args.so_busy_poll > 0)
+        && !(request_reply && (args.wait || args.rate.is_some()))
+    {
+        return Err(conflict(
+            "Busy polling needs -w or --rate on a single connection, and no --trace, --sizes, --reconnect, --depth or sink and source modes",
+        ));
+    }
+    Ok(())
+}
+
 fn main() {
     let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+    // A trace that does not read is refused like any other bad argument
+    let trace = args.trace.as_ref().map(|path| {
+        read_trace(path, args.speed).unwrap_or_else(|e| {
+            Args::command()
+                .error(clap::ErrorKind::InvalidValue, e)
+                .exit()
+        })
+    });
+    let tasks = Tasks {
+        load: args.load,
+        count: args.spawn,
+    };
+    let poll = Poll {
+        busy: args.busy_poll,
+        budget: args.so_busy_poll,
+    };
+    raise_fd_limit().unwrap();
+
+    let (clients, idle) = connections(&args);
+    let reply = match args.mode {
+        Mode::Echo | Mode::Sink => args.size,
+        Mode::Fixed | Mode::Source => args.reply.unwrap_or(args.size),
+    };
     let flag = Arc::new(AtomicBool::new(true));
 
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        threads: args.threads,
+        pinning,
+    };
+    runtime.apply();
+
     task::block_on(async {
-        for _ in 0..args.spawn {
+        spawn_load(args.load, args.spawn);
+
+        // Noise connections run next to whatever is measured
+        let noise = Noise {
+            address: args.noise_address.unwrap_or(args.address),
+            connections: args.noise,
+            size: args.noise_size.unwrap_or(args.size),
+            gap: args
+                .noise_rate
+                .map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            sink: args.noise_sink,
+            framed: args.framed,
+        };
+        let start = Instant::now();
+        let sent = noise.spawn();
+
+        if !args.wait
+            && args.rate.is_none()
+            && args.trace.is_none()
+            && args.sizes.is_none()
+            && matches!(args.mode, Mode::Echo)
+            && !args.reconnect
+            && args.depth == 0
+            && clients == 1
+            && idle == 0
+        {
+            // The open loop never returns, its noise is reported once the test is over
+            if noise.connections > 0 {
+                let (sent, duration, csv) =
+                    (sent.clone(), Duration::from_secs(args.duration), args.csv);
+                task::spawn(async move {
+                    task::sleep(duration).await;
+                    noise.report(sent.load(Relaxed), start.elapsed(), csv, tasks, runtime);
+                });
+            }
+            run(
+                args.address,
+                args.size,
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+                args.framed,
+            )
+            .await
+            .unwrap();
+        }
+
+        if let Some(sizes) = args.sizes.as_ref() {
+            run_sweep(
+                args.address,
+                args.size,
+                parse_sizes(sizes).unwrap(),
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+                args.framed,
+                args.warmup,
+                Duration::from_secs_f64(args.step),
+            )
+            .await
+            .unwrap();
+        } else if !args.reconnect && (clients > 1 || idle > 0) {
+            run_fan_in(
+                args.address,
+                args.size,
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+                clients,
+                idle,
+                args.framed,
+                Duration::from_secs(args.duration),
+            )
+            .await
+            .unwrap();
+        } else {
+            let c_duration = args.duration;
+            let c_flag = flag.clone();
             task::spawn(async move {
-                let mut x: usize = 1;
-                loop {
-                    x = x.wrapping_mul(2);
-                    task::sleep(Duration::from_millis(1)).await;
-                }
+                task::sleep(Duration::from_secs(c_duration)).await;
+                c_flag.store(false, Relaxed);
             });
-        }
 
-        if !args.wait {
-            run(args.address, args.size, args.interval, args.csv, args.spawn)
+            if let Mode::Sink | Mode::Source = args.mode {
+                run_stream(
+                    args.address,
+                    args.size,
+                    reply,
+                    args.mode,
+                    args.interval,
+                    args.csv,
+                    tasks,
+                    runtime,
+                    args.framed,
+                    flag,
+                )
+                .await
+                .unwrap();
+            } else if let Some(trace) = trace {
+                run_trace(
+                    args.address,
+                    args.size,
+                    args.csv,
+                    tasks,
+                    runtime,
+                    args.pacing,
+                    args.framed,
+                    trace,
+                    flag,
+                )
                 .await
                 .unwrap();
+            } else if args.reconnect {
+                run_reconnect(
+                    args.address,
+                    args.size,
+                    args.interval,
+                    args.csv,
+                    tasks,
+                    runtime,
+                    args.pacing,
+                    args.fast_open,
+                    clients,
+                    args.framed,
+                    flag,
+                )
+                .await
+                .unwrap();
+            } else if args.depth > 0 {
+                run_depth(
+                    args.address,
+                    args.size,
+                    args.interval,
+                    args.csv,
+                    tasks,
+                    runtime,
+                    args.depth,
+                    args.framed,
+                    flag,
+                )
+                .await
+                .unwrap();
+            } else {
+                run_wait(
+                    args.address,
+                    args.size,
+                    reply,
+                    args.interval,
+                    args.csv,
+                    tasks,
+                    runtime,
+                    args.pacing,
+                    poll,
+                    args.framed,
+                    args.rate
+                        .map(|rate| Schedule::new(args.arrival, rate, args.on, args.off)),
+                    flag,
+                )
+                .await
+                .unwrap();
+            }
         }
 
-        let c_duration = args.duration.clone();
-        let c_flag = flag.clone();
-        task::spawn(async move {
-            task::sleep(Duration::from_secs(c_duration)).await;
-            c_flag.store(false, Relaxed);
-        });
-
-        run_wait(
-            args.address,
-            args.size,
-            args.interval,
-            args.csv,
-            args.spawn,
-            flag,
-        )
-        .await
-        .unwrap();
+        if noise.connections > 0 {
+            noise.report(
+                sent.load(Relaxed),
+                start.elapsed(),
+                args.csv,
+                tasks,
+                runtime,
+            );
+        }
     });
 }
@@ -1,46 +1,585 @@
+use async_std::future;
 use async_std::net::UdpSocket;
-use async_std::sync::Arc;
 use async_std::task;
-use std::env;
-use std::net::SocketAddr;
+use clap::{Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-async fn read_exact(
-    socket: &Arc<UdpSocket>,
-    buffer: &mut [u8],
-) -> Result<SocketAddr, Box<dyn std::error::Error>> {
-    let mut read: usize = 0;
-    let mut g_addr = "127.0.0.1:8080".parse().unwrap();
-    while read < buffer.len() {
-        let (n, addr) = socket.recv_from(&mut buffer[read..]).await?;
-        g_addr = addr;
-        read += n;
+#[derive(Parser, Debug)]
+struct Args {
+    address: SocketAddr,
+    size: usize,
+    #[clap(long)]
+    ipv6_only: bool,
+    // The multicast subscriber only listens
+    #[clap(long, conflicts_with_all = &["busy-poll", "so-busy-poll"])]
+    join: Option<IpAddr>,
+    #[clap(long, default_value = "0.0.0.0")]
+    interface: Ipv4Addr,
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    idle: u64,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(long, value_enum, default_value = "none")]
+    work: Work,
+    #[clap(long, default_value = "0", value_parser = parse_service)]
+    service: f64,
+    #[clap(long, value_enum, default_value = "fixed")]
+    distribution: Distribution,
+    #[clap(long, default_value = "1")]
+    sigma: f64,
+    #[clap(long)]
+    busy_poll: bool,
+    #[clap(long, default_value = "0")]
+    so_busy_poll: u32,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: Option<usize>,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// How many threads run the async-std executor, one per core when unset. It
+// goes in the framework column, plain async-std for the default.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    threads: Option<usize>,
+    pinning: Pinning,
+}
+
+impl RuntimeConfig {
+    // async-std reads it once, when its runtime starts on the first task
+    fn apply(&self) {
+        if let Some(n) = self.threads {
+            std::env::set_var("ASYNC_STD_THREAD_COUNT", n.to_string());
+        }
+        let pinning = self.pinning;
+        let mut watchers = Vec::new();
+        if pinning.cpus.is_some() {
+            // One per core when --threads is unset
+            let count = self
+                .threads
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+            watchers.push(pin_named("async-std/runtime", count, move |n| {
+                pinning.worker(n).unwrap()
+            }));
+        }
+        if let Some(core) = pinning.reactor {
+            watchers.push(pin_named("async-io", 1, move |_| core));
+        }
+        // Started first, for its threads not to inherit the core of this one,
+        // and on a timer for async-io to start its thread too
+        task::block_on(task::sleep(Duration::from_millis(1)));
+        // The test starts once they are all in place
+        for watcher in watchers {
+            watcher.join().unwrap();
+        }
+        pinning.pin_main();
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "async-std")?;
+        if let Some(n) = self.threads {
+            write!(f, "/threads={}", n)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// What the pong does before answering a request: nothing, keep the CPU busy,
+// block the thread it runs on, block a thread of the blocking pool, or await
+// a timer.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Work {
+    None,
+    Spin,
+    Block,
+    Offload,
+    Timer,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Distribution {
+    Fixed,
+    Exponential,
+    Lognormal,
+}
+
+// Service times are `mean` microseconds on average, `sigma` is the spread of
+// the lognormal ones.
+#[derive(Clone, Copy, Debug)]
+struct Service {
+    work: Work,
+    distribution: Distribution,
+    mean: f64,
+    sigma: f64,
+}
+
+impl Service {
+    fn duration(&self, rng: &mut StdRng) -> Duration {
+        let micros = match self.distribution {
+            Distribution::Fixed => self.mean,
+            Distribution::Exponential => -(1.0 - rng.gen::<f64>()).ln() * self.mean,
+            // Box-Muller, with the location shifted for the mean to stay `mean`
+            Distribution::Lognormal => {
+                let z = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * rng.gen::<f64>()).cos();
+                (self.mean.ln() - self.sigma * self.sigma / 2.0 + self.sigma * z).exp()
+            }
+        };
+        Duration::from_secs_f64(micros / 1e6)
+    }
+}
+
+// Service times are waited out, they cannot be negative
+fn parse_service(mean: &str) -> Result<f64, String> {
+    match mean.parse::<f64>() {
+        Ok(micros) if micros >= 0.0 && micros.is_finite() => Ok(micros),
+        Ok(_) => Err(format!("{}: the service time has to be 0 or more", mean)),
+        Err(e) => Err(format!("{:?}: {}", mean, e)),
+    }
+}
+
+// How the sockets wait for data: parked on the runtime's reactor or busy
+// polled, and the SO_BUSY_POLL budget in microseconds, 0 is off.
+#[derive(Clone, Copy, Debug)]
+struct Poll {
+    busy: bool,
+    budget: u32,
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+// SO_BUSY_POLL: blocking reads and epoll waits on the socket poll the device
+// queue for up to `micros` before going to sleep.
+fn set_busy_poll(socket: &impl AsRawFd, micros: u32) -> std::io::Result<()> {
+    let value = micros as libc::c_int;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BUSY_POLL,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// CPU time of the whole process, every thread included
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+// The std socket behind an async one, for non-blocking calls on it
+fn borrow_std<T: FromRawFd>(socket: &impl AsRawFd) -> ManuallyDrop<T> {
+    ManuallyDrop::new(unsafe { T::from_raw_fd(socket.as_raw_fd()) })
+}
+
+// Busy polling retries a non-blocking call until it goes through, yielding in
+// between so that the runtime never parks in epoll waiting for the socket.
+// None once `timeout` went by without it going through.
+async fn spin_for<R>(
+    timeout: Duration,
+    mut op: impl FnMut() -> std::io::Result<R>,
+) -> Option<std::io::Result<R>> {
+    let start = Instant::now();
+    loop {
+        match op() {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if start.elapsed() >= timeout {
+                    return None;
+                }
+                task::yield_now().await;
+            }
+            res => return Some(res),
+        }
+    }
+}
+
+async fn process(work: Work, duration: Duration) {
+    match work {
+        Work::None => {}
+        Work::Spin => {
+            let start = Instant::now();
+            while start.elapsed() < duration {
+                std::hint::spin_loop();
+            }
+        }
+        // Stalls whatever else runs on this thread
+        Work::Block => thread::sleep(duration),
+        Work::Offload => task::spawn_blocking(move || thread::sleep(duration)).await,
+        Work::Timer => task::sleep(duration).await,
     }
-    Ok(g_addr)
 }
 
-async fn run(addr: SocketAddr, size: usize) -> Result<(), Box<dyn std::error::Error>> {
-    let socket = Arc::new(UdpSocket::bind(addr).await?);
+// IPv6 sockets are dual-stack unless `ipv6_only` is set.
+fn bind(addr: SocketAddr, ipv6_only: bool) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+// All the receivers of a group share its port.
+fn join(
+    addr: SocketAddr,
+    group: IpAddr,
+    interface: Ipv4Addr,
+) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    match group {
+        IpAddr::V4(group) => socket.join_multicast_v4(&group, &interface)?,
+        IpAddr::V6(group) => socket.join_multicast_v6(&group, 0)?,
+    }
+    Ok(socket.into())
+}
+
+// What the pong knows about each of its clients
+struct Peer {
+    datagrams: u64,
+    bytes: u64,
+    since: Instant,
+    last: Instant,
+    cpu: Duration,
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+fn report(peer: SocketAddr, state: &Peer, size: usize, csv: bool, runtime: RuntimeConfig) {
+    // CPU use of the whole pong since the peer showed up
+    let usage = (cpu_time() - state.cpu).as_secs_f64() / state.since.elapsed().as_secs_f64();
+    if csv {
+        // No load tasks run in the pong, its tasks read like those of an idle ping
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer
+        println!(
+            "{},udp,session,{},0,{},sleep:0,{},bytes,{},{}",
+            runtime,
+            state.datagrams,
+            size,
+            state.bytes,
+            family(peer),
+            peer
+        );
+        println!(
+            "{},udp,cpu,{},0,{},sleep:0,{:.1},%,{},{}",
+            runtime,
+            state.datagrams,
+            size,
+            usage * 100.0,
+            family(peer),
+            peer
+        );
+    } else {
+        println!(
+            "{}: datagrams={} bytes={} active={:?} cpu={:.1}%",
+            peer,
+            state.datagrams,
+            state.bytes,
+            state.last - state.since,
+            usage * 100.0
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run(
+    addr: SocketAddr,
+    size: usize,
+    ipv6_only: bool,
+    idle: Duration,
+    csv: bool,
+    service: Service,
+    poll: Poll,
+    runtime: RuntimeConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::from(bind(addr, ipv6_only)?);
+    if poll.budget > 0 {
+        set_busy_poll(&socket, poll.budget)?;
+    }
+    let busy = poll
+        .busy
+        .then(|| borrow_std::<std::net::UdpSocket>(&socket));
+    let socket = Arc::new(socket);
     let mut buf = vec![0u8; size];
+    let mut peers = HashMap::<SocketAddr, Peer>::new();
+    let mut rng = StdRng::from_entropy();
+    let mut sweep = Instant::now();
 
     loop {
-        let addr = read_exact(&socket, &mut buf).await?;
-        socket.send_to(&buf, addr).await?;
+        // Wake up at least once per idle period to expire peers
+        let received = match busy.as_deref() {
+            Some(socket) => spin_for(idle, || socket.recv_from(&mut buf)).await,
+            None => future::timeout(idle, socket.recv_from(&mut buf)).await.ok(),
+        };
+        if let Some(res) = received {
+            let (n, peer) = res?;
+            if let Work::None = service.work {
+                // Every datagram goes back to whoever sent it
+                if let Some(socket) = busy.as_deref() {
+                    // A datagram that cannot leave within the idle period is lost
+                    spin_for(idle, || socket.send_to(&buf[..n], peer))
+                        .await
+                        .transpose()?;
+                } else {
+                    socket.send_to(&buf[..n], peer).await?;
+                }
+            } else {
+                // A task per datagram, as the tcp pong has one per connection,
+                // so that the service time of one holds up no other
+                let duration = service.duration(&mut rng);
+                let (socket, datagram) = (socket.clone(), buf[..n].to_vec());
+                task::spawn(async move {
+                    process(service.work, duration).await;
+                    // Lost like any datagram that cannot leave
+                    let _ = socket.send_to(&datagram, peer).await;
+                });
+            }
+            let now = Instant::now();
+            let state = peers.entry(peer).or_insert_with(|| Peer {
+                datagrams: 0,
+                bytes: 0,
+                since: now,
+                last: now,
+                cpu: cpu_time(),
+            });
+            state.datagrams += 1;
+            state.bytes += n as u64;
+            state.last = now;
+        }
+
+        // Peers that went quiet are done, report what they did
+        if sweep.elapsed() >= idle {
+            peers.retain(|peer, state| {
+                let alive = state.last.elapsed() < idle;
+                if !alive {
+                    report(*peer, state, size, csv, runtime);
+                }
+                alive
+            });
+            sweep = Instant::now();
+        }
+    }
+}
+
+async fn run_multicast(
+    addr: SocketAddr,
+    size: usize,
+    group: IpAddr,
+    interface: Ipv4Addr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::from(join(addr, group, interface)?);
+    // Reply from a port of our own so that the ping can tell receivers apart
+    let unspecified: IpAddr = match addr {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let reply = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
+
+    let mut buf = vec![0u8; size];
+
+    loop {
+        let (n, addr) = socket.recv_from(&mut buf).await?;
+        reply.send_to(&buf[..n], addr).await?;
     }
 }
 
 fn main() {
-    let addr: SocketAddr = env::args()
-        .nth(1)
-        .unwrap()
-        .parse()
-        .expect("First argument must be a valid socket address");
-    let size: usize = env::args()
-        .nth(2)
-        .unwrap()
-        .parse()
-        .expect("Second argument must be the buffer size");
+    let args = Args::parse();
+    let service = Service {
+        work: args.work,
+        distribution: args.distribution,
+        mean: args.service,
+        sigma: args.sigma,
+    };
+    let poll = Poll {
+        busy: args.busy_poll,
+        budget: args.so_busy_poll,
+    };
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        threads: args.threads,
+        pinning,
+    };
+    runtime.apply();
 
     task::block_on(async {
-        run(addr, size).await.unwrap();
+        match args.join {
+            Some(group) => run_multicast(args.address, args.size, group, args.interface)
+                .await
+                .unwrap(),
+            None => run(
+                args.address,
+                args.size,
+                args.ipv6_only,
+                Duration::from_secs(args.idle),
+                args.csv,
+                service,
+                poll,
+                runtime,
+            )
+            .await
+            .unwrap(),
+        }
     });
 }
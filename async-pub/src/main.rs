@@ -0,0 +1,348 @@
+use async_std::net::{TcpListener, UdpSocket};
+use async_std::prelude::*;
+use async_std::task;
+use clap::{Parser, ValueEnum};
+use std::fmt;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Sequence number announcing the end of the test to the subscribers
+const END: u64 = u64::MAX;
+// seq, send time, interval
+const HEADER: usize = 24;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Tcp,
+    Udp,
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    address: SocketAddr,
+    // Room for the header at least
+    #[clap(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(HEADER as u64..))]
+    size: usize,
+    interval: f64,
+    #[clap(short = 'n', long, default_value = "1")]
+    subscribers: usize,
+    #[clap(short, long, default_value = "60")]
+    duration: u64,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: Option<usize>,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// How many threads run the async-std executor, one per core when unset. It
+// goes in the framework column, plain async-std for the default.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    threads: Option<usize>,
+    pinning: Pinning,
+}
+
+impl RuntimeConfig {
+    // async-std reads it once, when its runtime starts on the first task
+    fn apply(&self) {
+        if let Some(n) = self.threads {
+            std::env::set_var("ASYNC_STD_THREAD_COUNT", n.to_string());
+        }
+        let pinning = self.pinning;
+        let mut watchers = Vec::new();
+        if pinning.cpus.is_some() {
+            // One per core when --threads is unset
+            let count = self
+                .threads
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+            watchers.push(pin_named("async-std/runtime", count, move |n| {
+                pinning.worker(n).unwrap()
+            }));
+        }
+        if let Some(core) = pinning.reactor {
+            watchers.push(pin_named("async-io", 1, move |_| core));
+        }
+        // Started first, for its threads not to inherit the core of this one,
+        // and on a timer for async-io to start its thread too
+        task::block_on(task::sleep(Duration::from_millis(1)));
+        // The test starts once they are all in place
+        for watcher in watchers {
+            watcher.join().unwrap();
+        }
+        pinning.pin_main();
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "async-std")?;
+        if let Some(n) = self.threads {
+            write!(f, "/threads={}", n)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+// Instants cannot be compared across processes, CLOCK_MONOTONIC can.
+fn monotonic() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+fn stamp(payload: &mut [u8], count: u64, interval: f64) {
+    payload[0..8].copy_from_slice(&count.to_le_bytes());
+    payload[8..16].copy_from_slice(&monotonic().to_le_bytes());
+    payload[16..24].copy_from_slice(&interval.to_le_bytes());
+}
+
+async fn run_tcp(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    subscribers: usize,
+    duration: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(address).await?;
+    let mut streams = Vec::with_capacity(subscribers);
+    while streams.len() < subscribers {
+        let (stream, _src) = listener.accept().await?;
+        stream.set_nodelay(true)?;
+        streams.push(stream);
+    }
+
+    // The test starts once all the subscribers are there
+    let start = Instant::now();
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+    while start.elapsed() < duration {
+        stamp(&mut payload, count, interval);
+        for stream in streams.iter_mut() {
+            stream.write_all(&payload).await?;
+        }
+
+        task::sleep(Duration::from_secs_f64(interval)).await;
+        count = count.wrapping_add(1);
+    }
+
+    stamp(&mut payload, END, interval);
+    for stream in streams.iter_mut() {
+        stream.write_all(&payload).await?;
+    }
+    Ok(())
+}
+
+async fn run_udp(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    subscribers: usize,
+    duration: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::bind(address).await?;
+    let mut payload = vec![0u8; size];
+
+    // Subscribers announce themselves with an empty datagram
+    let mut peers = Vec::with_capacity(subscribers);
+    while peers.len() < subscribers {
+        let (_, peer) = socket.recv_from(&mut payload).await?;
+        if !peers.contains(&peer) {
+            peers.push(peer);
+        }
+    }
+
+    let start = Instant::now();
+    let mut count: u64 = 0;
+    while start.elapsed() < duration {
+        stamp(&mut payload, count, interval);
+        for peer in peers.iter() {
+            socket.send_to(&payload, peer).await?;
+        }
+
+        task::sleep(Duration::from_secs_f64(interval)).await;
+        count = count.wrapping_add(1);
+    }
+
+    stamp(&mut payload, END, interval);
+    for peer in peers.iter() {
+        socket.send_to(&payload, peer).await?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        threads: args.threads,
+        pinning,
+    };
+    runtime.apply();
+
+    task::block_on(async {
+        let duration = Duration::from_secs(args.duration);
+        match args.kind {
+            Kind::Tcp => run_tcp(
+                args.address,
+                args.size,
+                args.interval,
+                args.subscribers,
+                duration,
+            )
+            .await
+            .unwrap(),
+            Kind::Udp => run_udp(
+                args.address,
+                args.size,
+                args.interval,
+                args.subscribers,
+                duration,
+            )
+            .await
+            .unwrap(),
+        }
+    });
+}
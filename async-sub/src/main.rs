@@ -0,0 +1,467 @@
+use async_std::future;
+use async_std::net::{TcpStream, UdpSocket};
+use async_std::prelude::*;
+use async_std::task;
+use clap::{Parser, ValueEnum};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Sequence number announcing the end of the test to the subscribers
+const END: u64 = u64::MAX;
+// seq, send time, interval
+const HEADER: usize = 24;
+const PERCENTILES: [(&str, f64); 5] = [
+    ("p50", 0.5),
+    ("p90", 0.9),
+    ("p99", 0.99),
+    ("p999", 0.999),
+    ("max", 1.0),
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Tcp,
+    Udp,
+}
+
+impl Kind {
+    fn transport(&self) -> &'static str {
+        match self {
+            Kind::Tcp => "tcp",
+            Kind::Udp => "udp",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    address: SocketAddr,
+    // Room for the header at least
+    #[clap(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(HEADER as u64..))]
+    size: usize,
+    #[clap(short = 'n', long, default_value = "1")]
+    subscribers: usize,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(short, long, default_value = "5")]
+    timeout: u64,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: Option<usize>,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// How many threads run the async-std executor, one per core when unset. It
+// goes in the framework column, plain async-std for the default.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    threads: Option<usize>,
+    pinning: Pinning,
+}
+
+impl RuntimeConfig {
+    // async-std reads it once, when its runtime starts on the first task
+    fn apply(&self) {
+        if let Some(n) = self.threads {
+            std::env::set_var("ASYNC_STD_THREAD_COUNT", n.to_string());
+        }
+        let pinning = self.pinning;
+        let mut watchers = Vec::new();
+        if pinning.cpus.is_some() {
+            // One per core when --threads is unset
+            let count = self
+                .threads
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+            watchers.push(pin_named("async-std/runtime", count, move |n| {
+                pinning.worker(n).unwrap()
+            }));
+        }
+        if let Some(core) = pinning.reactor {
+            watchers.push(pin_named("async-io", 1, move |_| core));
+        }
+        // Started first, for its threads not to inherit the core of this one,
+        // and on a timer for async-io to start its thread too
+        task::block_on(task::sleep(Duration::from_millis(1)));
+        // The test starts once they are all in place
+        for watcher in watchers {
+            watcher.join().unwrap();
+        }
+        pinning.pin_main();
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "async-std")?;
+        if let Some(n) = self.threads {
+            write!(f, "/threads={}", n)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// One-way latencies (seq, ns) seen by a subscriber, and the publication interval
+struct Samples {
+    interval: f64,
+    latencies: Vec<(u64, u64)>,
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+// Instants cannot be compared across processes, CLOCK_MONOTONIC can.
+fn monotonic() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+// Returns false once the publisher is done.
+fn record(payload: &[u8], samples: &mut Samples) -> bool {
+    let now = monotonic();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&payload[0..8]);
+    let count = u64::from_le_bytes(bytes);
+    if count == END {
+        return false;
+    }
+    bytes.copy_from_slice(&payload[8..16]);
+    let sent = u64::from_le_bytes(bytes);
+    bytes.copy_from_slice(&payload[16..24]);
+    samples.interval = f64::from_le_bytes(bytes);
+    samples.latencies.push((count, now.saturating_sub(sent)));
+    true
+}
+
+async fn subscribe_tcp(address: SocketAddr, size: usize) -> io::Result<Samples> {
+    let mut stream = TcpStream::connect(address).await?;
+    stream.set_nodelay(true)?;
+    let mut payload = vec![0u8; size];
+    let mut samples = Samples {
+        interval: 0.0,
+        latencies: Vec::new(),
+    };
+
+    loop {
+        match stream.read_exact(&mut payload).await {
+            Ok(_) => {
+                if !record(&payload, &mut samples) {
+                    return Ok(samples);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(samples),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn subscribe_udp(address: SocketAddr, size: usize, timeout: Duration) -> io::Result<Samples> {
+    let unspecified: IpAddr = match address {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
+    socket.connect(address).await?;
+    socket.send(&[]).await?;
+    let mut payload = vec![0u8; size];
+    let mut samples = Samples {
+        interval: 0.0,
+        latencies: Vec::new(),
+    };
+
+    // A lost end of test must not hang the subscriber
+    while let Ok(res) = future::timeout(timeout, socket.recv(&mut payload)).await {
+        res?;
+        if !record(&payload, &mut samples) {
+            break;
+        }
+    }
+    Ok(samples)
+}
+
+fn percentile(sorted: &[u64], q: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let i = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[i - 1]
+}
+
+fn report(
+    kind: Kind,
+    family: &str,
+    size: usize,
+    subscribers: Vec<Samples>,
+    csv: bool,
+    runtime: RuntimeConfig,
+) {
+    let n = subscribers.len();
+    let interval = subscribers.iter().map(|s| s.interval).fold(0.0, f64::max);
+    let mut percentiles = Vec::with_capacity(n);
+
+    for (id, samples) in subscribers.iter().enumerate() {
+        for (count, latency) in samples.latencies.iter() {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+                println!(
+                    "{},{},oneway,{},{},{},0,{},ns,{},{},{}",
+                    runtime,
+                    kind.transport(),
+                    count,
+                    interval,
+                    size,
+                    latency,
+                    family,
+                    id,
+                    n
+                );
+            } else {
+                println!(
+                    "{} bytes: sub={} seq={} time={:?}",
+                    size,
+                    id,
+                    count,
+                    Duration::from_nanos(*latency)
+                );
+            }
+        }
+
+        let mut sorted: Vec<u64> = samples.latencies.iter().map(|(_, l)| *l).collect();
+        sorted.sort_unstable();
+        let p: Vec<u64> = PERCENTILES
+            .iter()
+            .map(|(_, q)| percentile(&sorted, *q))
+            .collect();
+        for ((name, _), value) in PERCENTILES.iter().zip(p.iter()) {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+                println!(
+                    "{},{},oneway-{},{},{},{},0,{},ns,{},{},{}",
+                    runtime,
+                    kind.transport(),
+                    name,
+                    sorted.len(),
+                    interval,
+                    size,
+                    value,
+                    family,
+                    id,
+                    n
+                );
+            } else {
+                println!("sub={} {}={:?}", id, name, Duration::from_nanos(*value));
+            }
+        }
+        percentiles.push(p);
+    }
+
+    // How far the slowest subscriber lags behind the fastest one
+    for (i, (name, _)) in PERCENTILES.iter().enumerate() {
+        let fastest = percentiles.iter().map(|p| p[i]).min().unwrap_or(0);
+        let slowest = percentiles.iter().map(|p| p[i]).max().unwrap_or(0);
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+            println!(
+                "{},{},spread-{},{},{},{},0,{},ns,{},all,{}",
+                runtime,
+                kind.transport(),
+                name,
+                n,
+                interval,
+                size,
+                slowest - fastest,
+                family,
+                n
+            );
+        } else {
+            println!(
+                "spread {}={:?}",
+                name,
+                Duration::from_nanos(slowest - fastest)
+            );
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        threads: args.threads,
+        pinning,
+    };
+    runtime.apply();
+
+    task::block_on(async {
+        let timeout = Duration::from_secs(args.timeout);
+        let handles: Vec<_> = (0..args.subscribers)
+            .map(|_| match args.kind {
+                Kind::Tcp => task::spawn(subscribe_tcp(args.address, args.size)),
+                Kind::Udp => task::spawn(subscribe_udp(args.address, args.size, timeout)),
+            })
+            .collect();
+
+        let mut subscribers = Vec::with_capacity(handles.len());
+        for handle in handles {
+            subscribers.push(handle.await.unwrap());
+        }
+
+        report(
+            args.kind,
+            family(args.address),
+            args.size,
+            subscribers,
+            args.csv,
+            runtime,
+        );
+    });
+}
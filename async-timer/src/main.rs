@@ -0,0 +1,495 @@
+use async_io::Async;
+use async_std::task;
+use clap::{Parser, ValueEnum};
+use std::fmt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Parser, Debug)]
+struct Args {
+    interval: f64,
+    #[clap(short, long, default_value = "0")]
+    spawn: usize,
+    #[clap(long, value_enum, default_value = "sleep")]
+    load: Load,
+    #[clap(long, value_enum, default_value = "timer")]
+    pacing: Pacing,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(short, long, default_value = "60")]
+    duration: u64,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: Option<usize>,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// How many threads run the async-std executor, one per core when unset. It
+// goes in the framework column, plain async-std for the default.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    threads: Option<usize>,
+    pinning: Pinning,
+}
+
+impl RuntimeConfig {
+    // async-std reads it once, when its runtime starts on the first task
+    fn apply(&self) {
+        if let Some(n) = self.threads {
+            std::env::set_var("ASYNC_STD_THREAD_COUNT", n.to_string());
+        }
+        let pinning = self.pinning;
+        let mut watchers = Vec::new();
+        if pinning.cpus.is_some() {
+            // One per core when --threads is unset
+            let count = self
+                .threads
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+            watchers.push(pin_named("async-std/runtime", count, move |n| {
+                pinning.worker(n).unwrap()
+            }));
+        }
+        if let Some(core) = pinning.reactor {
+            watchers.push(pin_named("async-io", 1, move |_| core));
+        }
+        // Started first, for its threads not to inherit the core of this one,
+        // and on a timer for async-io to start its thread too
+        task::block_on(task::sleep(Duration::from_millis(1)));
+        // The test starts once they are all in place
+        for watcher in watchers {
+            watcher.join().unwrap();
+        }
+        pinning.pin_main();
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "async-std")?;
+        if let Some(n) = self.threads {
+            write!(f, "/threads={}", n)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// Background tasks competing with the test for the runtime: sleeping, spinning
+// without ever yielding, yielding in a tight loop, passing tokens around a ring
+// of channels, blocking the worker thread, or doing socket I/O of their own.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Load {
+    Sleep,
+    Spin,
+    Yield,
+    Channel,
+    Block,
+    Io,
+}
+
+impl Load {
+    fn name(&self) -> &'static str {
+        match self {
+            Load::Sleep => "sleep",
+            Load::Spin => "spin",
+            Load::Yield => "yield",
+            Load::Channel => "channel",
+            Load::Block => "block",
+            Load::Io => "io",
+        }
+    }
+}
+
+// The tasks column, the load profile and how many tasks run it
+#[derive(Clone, Copy, Debug)]
+struct Tasks {
+    load: Load,
+    count: usize,
+}
+
+impl fmt::Display for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.load.name(), self.count)
+    }
+}
+
+// How the probe sleeps: the runtime's own timer, a blocking sleep handed to
+// another thread, spinning on the clock, or a timerfd armed with the absolute
+// deadline and read like any other file descriptor.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Pacing {
+    Timer,
+    Offload,
+    Spin,
+    Timerfd,
+}
+
+impl Pacing {
+    fn name(&self) -> &'static str {
+        match self {
+            Pacing::Timer => "timer",
+            Pacing::Offload => "offload",
+            Pacing::Spin => "spin",
+            Pacing::Timerfd => "timerfd",
+        }
+    }
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+fn timerfd(flags: libc::c_int) -> std::io::Result<OwnedFd> {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags | libc::TFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Arms the timerfd for an absolute deadline, Instant is CLOCK_MONOTONIC on Linux.
+// False if the deadline has passed already, a zero expiry would disarm it.
+fn arm(fd: RawFd, deadline: Instant) -> std::io::Result<bool> {
+    let wait = deadline.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        return Ok(false);
+    }
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let nanos = now.tv_nsec as u64 + wait.subsec_nanos() as u64;
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: now.tv_sec + (wait.as_secs() + nanos / 1_000_000_000) as libc::time_t,
+            tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+        },
+    };
+    let res =
+        unsafe { libc::timerfd_settime(fd, libc::TFD_TIMER_ABSTIME, &spec, std::ptr::null_mut()) };
+    if res < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+// How many times the timerfd expired since it was armed
+fn expirations(fd: RawFd) -> std::io::Result<u64> {
+    let mut count = [0u8; 8];
+    let n = unsafe { libc::read(fd, count.as_mut_ptr() as *mut libc::c_void, count.len()) };
+    if n < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(u64::from_ne_bytes(count))
+}
+
+// Waits with the chosen pacing, the timerfd is created once and armed again
+// for every deadline.
+struct Pacer {
+    pacing: Pacing,
+    timerfd: Option<Async<OwnedFd>>,
+}
+
+impl Pacer {
+    fn new(pacing: Pacing) -> std::io::Result<Pacer> {
+        let timerfd = match pacing {
+            Pacing::Timerfd => Some(Async::new(timerfd(libc::TFD_NONBLOCK)?)?),
+            _ => None,
+        };
+        Ok(Pacer { pacing, timerfd })
+    }
+
+    async fn sleep(&self, wait: Duration) {
+        self.until(Instant::now() + wait).await;
+    }
+
+    async fn until(&self, deadline: Instant) {
+        match self.pacing {
+            Pacing::Timer => task::sleep(deadline.saturating_duration_since(Instant::now())).await,
+            Pacing::Offload => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                task::spawn_blocking(move || std::thread::sleep(wait)).await;
+            }
+            Pacing::Spin => {
+                while Instant::now() < deadline {
+                    std::hint::spin_loop();
+                }
+            }
+            Pacing::Timerfd => {
+                let timerfd = self.timerfd.as_ref().unwrap();
+                if arm(timerfd.as_raw_fd(), deadline).unwrap() {
+                    timerfd
+                        .read_with(|fd| expirations(fd.as_raw_fd()))
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
+
+// Sleeps `interval` over and over for `duration` and reports how late each
+// wake-up was, which is what the timer and the scheduler add to the sleep.
+async fn run(
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    duration: Duration,
+) {
+    let target = Duration::from_secs_f64(interval);
+    let mut samples: Vec<Duration> = Vec::new();
+    let start = Instant::now();
+
+    let pacer = Pacer::new(pacing).unwrap();
+    while start.elapsed() < duration {
+        let before = Instant::now();
+        pacer.sleep(target).await;
+        samples.push(before.elapsed().saturating_sub(target));
+    }
+
+    for (count, late) in samples.into_iter().enumerate() {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, pacing
+            println!(
+                "{},timer,lateness,{},{},0,{},{},ns,none,{}",
+                runtime,
+                count,
+                interval,
+                tasks,
+                late.as_nanos(),
+                pacing.name()
+            );
+        } else {
+            println!("seq={} late={:?}", count, late);
+        }
+    }
+}
+
+async fn background(load: Load) {
+    let mut x: usize = 1;
+    match load {
+        Load::Sleep => loop {
+            x = x.wrapping_mul(2);
+            task::sleep(Duration::from_millis(1)).await;
+        },
+        // Never yields, so it keeps a worker for itself
+        Load::Spin => loop {
+            x = std::hint::black_box(x.wrapping_mul(2));
+        },
+        Load::Yield => loop {
+            task::yield_now().await;
+        },
+        Load::Block => loop {
+            std::thread::sleep(Duration::from_millis(1));
+            task::yield_now().await;
+        },
+        Load::Io => {
+            let socket = async_std::net::UdpSocket::bind("127.0.0.1:0")
+                .await
+                .unwrap();
+            socket.connect(socket.local_addr().unwrap()).await.unwrap();
+            let mut buf = [0u8; 64];
+            loop {
+                socket.send(&buf).await.unwrap();
+                socket.recv(&mut buf).await.unwrap();
+                // The datagram is always there already, which is no reason
+                // to keep the worker
+                task::yield_now().await;
+            }
+        }
+        Load::Channel => unreachable!("Channel tasks are spawned as a ring"),
+    }
+}
+
+fn spawn_load(load: Load, count: usize) {
+    if let Load::Channel = load {
+        // Every task forwards the tokens it gets to the next one
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..count)
+            .map(|_| async_std::channel::bounded::<u64>(1))
+            .unzip();
+        for tx in &txs {
+            tx.try_send(0).unwrap();
+        }
+        for (i, rx) in rxs.into_iter().enumerate() {
+            let tx = txs[(i + 1) % count].clone();
+            task::spawn(async move {
+                while let Ok(token) = rx.recv().await {
+                    if tx.send(token).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        return;
+    }
+    for _ in 0..count {
+        task::spawn(background(load));
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let tasks = Tasks {
+        load: args.load,
+        count: args.spawn,
+    };
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        threads: args.threads,
+        pinning,
+    };
+    runtime.apply();
+
+    task::block_on(async {
+        spawn_load(args.load, args.spawn);
+        run(
+            args.interval,
+            args.csv,
+            tasks,
+            runtime,
+            args.pacing,
+            Duration::from_secs(args.duration),
+        )
+        .await;
+    });
+}
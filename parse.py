@@ -31,8 +31,15 @@ styles = {
     'tcp': (0,0),
     'udp': (1,1),
     'icmp': (2,3),
+    'unix': (3,1),
+    'pipe': (4,2),
+    'multicast': (1,3),
+    'tcp-tfo': (5,2),
 }
 
+# tests whose samples are latencies in ns
+LATENCIES = ('rtt', 'oneway', 'connect', 'first-byte', 'response', 'lateness')
+
 
 
 # palette = 'bright' #sns.color_palette("bright", 6) #'plasma'
@@ -87,24 +94,15 @@ def bytes_label(n):
     return "{0:.{c}f} YiB".format(ydiv, c=0 if n % ymod == 0 else 2)
 
 def interval_label(n):
+    # the rate column holds the interval between messages, labels are in msg/s
     if n == 0:
         return "inf"
-    if n == 1:
-        return "1"
-    if n == 0.1:
-        return "10"
-    if n == 0.001:
-        return "100"
-    if n == 0.0001:
-        return "1 K"
-    if n == 0.00001:
-        return "10 K"
-    if n == 0.000001:
-        return "100 K"
-    if n == 0.0000001:
-        return "1 M"
-
-    return "1 M"
+    rate = 1 / n
+    if rate >= 1000000:
+        return "{0:g} M".format(round(rate / 1000000, 2))
+    if rate >= 1000:
+        return "{0:g} K".format(round(rate / 1000, 2))
+    return "{0:g}".format(round(rate, 2))
 
 def convert_value(line):
     if line.unit == 's':
@@ -120,10 +118,13 @@ def read_log(log_dir):
     log = None
     for l in os.scandir(log_dir):
         if l.is_file():
+            data = pd.read_csv(l)
+            # fan-ins against thread per core pongs are told apart by their log name
+            data['pong'] = 'per-core' if '-fanin-per-core-' in l.name else 'shared'
             if log is None:
-                log = pd.read_csv(l)
+                log = data
             else:
-                log = pd.concat([log,pd.read_csv(l)])
+                log = pd.concat([log,data])
     return log
 
 def mask_first_and_last(x):
@@ -138,25 +139,79 @@ def mask_first_and_last(x):
 def prepare(log_dir, kind):
     log = read_log(log_dir)
 
+    # logs written before the family column was added are all IPv4
+    if 'family' not in log.columns:
+        log['family'] = 'ipv4'
+    log['family'] = log['family'].fillna('ipv4')
+
+    # samples without an arrival process were paced by sleeping the interval
+    if 'arrival' not in log.columns:
+        log['arrival'] = 'interval'
+    log['arrival'] = log['arrival'].fillna('interval')
+
+    # before the pacing column the smol pings offloaded a blocking sleep, the rest used their timer
+    if 'pacing' not in log.columns:
+        log['pacing'] = None
+    legacy = (log['framework'] == 'smol') & (log['transport'] != 'timer')
+    log['pacing'] = log['pacing'].fillna(legacy.map({True: 'offload', False: 'timer'}))
+
+    # sockets waited on the reactor before they could be busy polled
+    if 'poll' not in log.columns:
+        log['poll'] = 'reactor:0'
+    log['poll'] = log['poll'].fillna('reactor:0')
+
+    # rows carry the settings of their runtime, tokio/current/workers=1, smol/local...
+    framework = log['framework'].astype(str).str.split('/', n=1)
+    log['framework'] = [f[0] for f in framework]
+    log['runtime'] = [f[1] if len(f) > 1 else 'default' for f in framework]
+
+    # tasks is load:count since the load profiles, bare counts were sleeping tasks
+    tasks = log['tasks'].astype(str).str.split(':')
+    log['load'] = [t[0] if len(t) > 1 else 'sleep' for t in tasks]
+    log['tasks'] = [int(t[-1]) for t in tasks]
+
     # filtering by kind of test
     log = log[log['test']==kind]
 
     log['value'] = pd.to_numeric(log['value'], errors='coerce')
 
-    if kind == 'rtt':
+    if kind in LATENCIES:
         # Remove first and last two samples of every test
-        mask = log.groupby(['framework', 'transport','test','payload','tasks', 'rate']).transform(
+        mask = log.groupby(['framework', 'transport', 'family','test','payload','tasks', 'load', 'rate', 'arrival', 'pacing', 'poll', 'runtime', 'pong']).transform(
         mask_first_and_last)['value']
     elif kind == 'throughput':
         # Remove first and last two samples of every test
-        mask = log.groupby(['framework', 'transport','test','payload','tasks', 'rate']).transform(
+        mask = log.groupby(['framework', 'transport', 'family','test','payload','tasks', 'load', 'rate', 'arrival', 'pacing', 'poll', 'runtime', 'pong']).transform(
         mask_first_and_last)['value']
+    else:
+        # one summary row per test
+        mask = [True]*len(log)
     log = log.loc[mask]
 
-    if kind == 'rtt':
+    if kind in LATENCIES:
         # this converts everything to seconds, data is expected as micro seconds
         log['value']= log.apply(convert_value, axis=1)
         log['label'] = [interval_label(v) for k, v in log['rate'].iteritems()]
+        scheduled = log['arrival'] != 'interval'
+        log.loc[scheduled, 'label'] = log.loc[scheduled, 'label'] + ' ' + log.loc[scheduled, 'arrival']
+        paced = log['pacing'] != 'timer'
+        log.loc[paced, 'label'] = log.loc[paced, 'label'] + ' ' + log.loc[paced, 'pacing']
+        polled = log['poll'] != 'reactor:0'
+        log.loc[polled, 'label'] = log.loc[polled, 'label'] + ' ' + log.loc[polled, 'poll']
+        tuned = log['runtime'] != 'default'
+        log.loc[tuned, 'label'] = log.loc[tuned, 'label'] + ' ' + log.loc[tuned, 'runtime']
+        per_core = log['pong'] == 'per-core'
+        log.loc[per_core, 'label'] = log.loc[per_core, 'label'] + ' per-core pong'
+        log.sort_values(by='rate', inplace=True, ascending=False)
+
+    elif kind == 'cpu':
+        log['label'] = [interval_label(v) + ' ' + p for v, p in zip(log['rate'], log['poll'])]
+        # pongs report one cpu row per peer, the pings leave the peer column empty
+        if 'peer' in log.columns:
+            pong = log['peer'].notna()
+            log.loc[pong, 'label'] = 'pong'
+        tuned = log['runtime'] != 'default'
+        log.loc[tuned, 'label'] = log.loc[tuned, 'label'] + ' ' + log.loc[tuned, 'runtime']
         log.sort_values(by='rate', inplace=True, ascending=False)
 
     elif kind == 'throughput':
@@ -168,7 +223,7 @@ def prepare(log_dir, kind):
     log = log.reset_index()
     return log
 
-def filter(log, transport=None, rate=None, tasks=None):
+def filter(log, transport=None, rate=None, tasks=None, family=None, arrival=None, load=None, pacing=None, poll=None, runtime=None, pong=None):
     layers = log['framework'].unique()
 
     if transport is not None:
@@ -182,6 +237,27 @@ def filter(log, transport=None, rate=None, tasks=None):
     if tasks is not None:
         log = log[log['tasks']==tasks]
 
+    if family is not None:
+        log = log[log['family']==family]
+
+    if arrival is not None:
+        log = log[log['arrival']==arrival]
+
+    if load is not None:
+        log = log[log['load']==load]
+
+    if pacing is not None:
+        log = log[log['pacing']==pacing]
+
+    if poll is not None:
+        log = log[log['poll']==poll]
+
+    if runtime is not None:
+        log = log[log['runtime']==runtime]
+
+    if pong is not None:
+        log = log[log['pong']==pong]
+
     return log
 
 
@@ -291,15 +367,46 @@ def throughput_stat_plot(log, scale, outfile):
 
 
 
+def cpu_stat_plot(log, scale, outfile):
+
+    fig, axes = plt.subplots()
+
+    g = sns.barplot(data=log, x='label', y='value', palette=palette,
+                hue='framework', estimator=np.median)
+
+    if scale == 'log':
+        g.set_yscale('log')
+
+    plt.grid(which='major', color='grey', linestyle='-', linewidth=0.1)
+    plt.grid(which='minor', color='grey', linestyle=':', linewidth=0.1, axis='y')
+
+    plt.xticks(rotation=72.5)
+    plt.xlabel('Message interval and polling')
+
+    plt.ylabel('CPU usage (%)')
+    plt.legend(title='Legend', loc='center left', bbox_to_anchor=(1.0, 0.5))
+
+    plt.tight_layout()
+    fig.savefig(IMG_DIR.joinpath(outfile))
+
+
+
 def main():
     parser = argparse.ArgumentParser(description='Parse zenoh flow performance results')
-    parser.add_argument('-k','--kind', help='Kind of the tests', required=False, choices=['rtt', 'throughput'], default='rtt')
+    parser.add_argument('-k','--kind', help='Kind of the tests, response rows are only written by pings on a --rate schedule', required=False, choices=['rtt', 'oneway', 'connect', 'first-byte', 'response', 'lateness', 'throughput', 'cpu'], default='rtt')
     parser.add_argument('-d','--data', help='Logs directory', required=True, type=str)
-    parser.add_argument('-p','--transport', help='udp, tcp or icmp', choices=['udp', 'tcp', 'icmp'], required=False)
+    parser.add_argument('-p','--transport', help='udp, tcp, tcp-tfo (Fast Open), icmp, unix (socketpair), pipe, multicast or timer (no networking)', choices=['udp', 'tcp', 'tcp-tfo', 'icmp', 'unix', 'pipe', 'multicast', 'timer'], required=False)
     parser.add_argument('-t','--type', help='Plot type', choices=['stat', 'time', 'ecdf', 'pdf'], default='stat', required=False)
     parser.add_argument('-s','--scale', help='Plot scale', choices=['log', 'lin'], default='log', required=False)
+    parser.add_argument('-f','--family', help='Filter for this address family', choices=['ipv4', 'ipv6', 'unix'], required=False)
     parser.add_argument('-r','--rate', help='Filter for this rate', required=False, type=float)
+    parser.add_argument('-a','--arrival', help='Filter for this arrival process, interval is the legacy sleep pacing', choices=['interval', 'constant', 'poisson', 'bursty', 'trace'], required=False)
     parser.add_argument('-l','--tasks', help='Filter for tasks number', required=False, type=int, default=0)
+    parser.add_argument('-L','--load', help='Filter for this load profile of the tasks', choices=['sleep', 'spin', 'yield', 'channel', 'block', 'io'], required=False)
+    parser.add_argument('-P','--pacing', help='Filter for this pacing strategy', choices=['timer', 'offload', 'spin', 'timerfd'], required=False)
+    parser.add_argument('-B','--poll', help='Filter for this socket polling, busy:N or reactor:N with N the SO_BUSY_POLL budget in us', required=False)
+    parser.add_argument('-R','--runtime', help='Filter for this runtime configuration, as in the framework column after the framework (current/workers=1, local, threads=2, cpus=2+3/main=1), default for the default runtime', required=False)
+    parser.add_argument('-X','--pong', help='Filter for the layout of the pong of a fan-in, shared or per-core (thread per core)', choices=['shared', 'per-core'], required=False)
     parser.add_argument('-o','--output', help='Output file name', required=False, type=str, default='plot.pdf')
 
     args = vars(parser.parse_args())
@@ -311,14 +418,15 @@ def main():
 
     log = prepare(args['data'], args['kind'])
     print(f'[ STEP1 ] Read a total of {log.size} samples')
-    log = filter(log, args.get('transport', None), args.get('rate', None), args.get('tasks', None))
+    log = filter(log, args.get('transport', None), args.get('rate', None), args.get('tasks', None), args.get('family', None), args.get('arrival', None), args.get('load', None), args.get('pacing', None), args.get('poll', None), args.get('runtime', None), args.get('pong', None))
     print(f'[ STEP2 ] After filtering we have {log.size} samples')
     if log.size == 0:
         print(f'[ ERR ] Cannot continue without samples!')
         exit(-1)
 
 
-    if args['kind'] == 'rtt':
+    # one-way, connect, first-byte and timer wake-up latencies plot like RTTs
+    if args['kind'] in LATENCIES:
         if args['type'] == 'stat':
             rtt_stat_plot(log, args['scale'], args['output'])
         elif args['type'] == 'time':
@@ -339,6 +447,8 @@ def main():
         elif args['type'] == 'pdf':
             print('Not implemented.')
             # rtt_pdf_plot(log, args['scale'], args['output'])
+    elif args['kind'] == 'cpu':
+        cpu_stat_plot(log, args['scale'], args['output'])
 
     out = IMG_DIR.joinpath(args['output'])
     print(f'[  DONE ] File saved to { out }')
@@ -11,7 +11,23 @@ usage() { printf "Usage: $0 \n\t
    -S std\n\t
    -s smol\n\t
    -t tokio\n\t
-   -P ping
+   -P ping\n\t
+   -m udp multicast with all frameworks\n\t
+   -f pub/sub fan-out with all frameworks\n\t
+   -n fan-in of CLIENTS pings to one pong with all frameworks\n\t
+   -C tcp scalability with a growing number of connections, ACTIVE of them pinging\n\t
+   -r tcp connection per request, with and without Fast Open, accept rate of CLIENTS connecting back to back\n\t
+   -D tcp and udp with a growing number of requests in flight\n\t
+   -R tcp and udp at target rates with constant, poisson and bursty arrivals\n\t
+   -Z tcp payload sizes from 64 B to 1 MiB, swept on a single connection\n\t
+   -T timer wake-up lateness, alone and next to LOAD_TASKS tasks of every load profile\n\t
+   -p tcp, udp and timer lateness with every pacing strategy\n\t
+   -b tcp and udp with busy polling on both sides and SO_BUSY_POLL budgets\n\t
+   -k tokio tcp and udp with every runtime configuration in TOKIO_CONFIGS\n\t
+   -e smol, async-std and tokio tcp and udp on THREAD_COUNTS executor threads, and smol on its local executor\n\t
+   -x fan-in of CLIENTS pings to std, smol and tokio pongs on PONG_CORES, shared against thread per core\n\t
+   -l async, smol and tokio pings with their threads placed by every layout in PIN_LAYOUTS, the pongs on core 0\n\t
+   -6 use IPv6 loopback for all the tests, wherever it appears (IPV6_ONLY=1 for IPv6-only pongs)\n\t
    -h help\n" 1>&2; exit 1; }
 
 # trap ctrl-c and call ctrl_c()
@@ -27,6 +43,11 @@ function std_cleanup() {
    sudo killall std-pong-tcp > /dev/null 2>&1
    sudo killall std-ping-udp > /dev/null 2>&1
    sudo killall std-pong-udp > /dev/null 2>&1
+   sudo killall std-ping-ipc > /dev/null 2>&1
+   sudo killall std-pong-ipc > /dev/null 2>&1
+   sudo killall std-pub > /dev/null 2>&1
+   sudo killall std-sub > /dev/null 2>&1
+   sudo killall std-timer > /dev/null 2>&1
 }
 
 function async_std_cleanup() {
@@ -34,6 +55,11 @@ function async_std_cleanup() {
    sudo killall async-pong-tcp > /dev/null 2>&1
    sudo killall async-ping-udp > /dev/null 2>&1
    sudo killall async-pong-udp > /dev/null 2>&1
+   sudo killall async-ping-ipc > /dev/null 2>&1
+   sudo killall async-pong-ipc > /dev/null 2>&1
+   sudo killall async-pub > /dev/null 2>&1
+   sudo killall async-sub > /dev/null 2>&1
+   sudo killall async-timer > /dev/null 2>&1
 }
 
 function tokio_cleanup() {
@@ -41,6 +67,11 @@ function tokio_cleanup() {
    sudo killall tokio-pong-tcp > /dev/null 2>&1
    sudo killall tokio-ping-udp > /dev/null 2>&1
    sudo killall tokio-pong-udp > /dev/null 2>&1
+   sudo killall tokio-ping-ipc > /dev/null 2>&1
+   sudo killall tokio-pong-ipc > /dev/null 2>&1
+   sudo killall tokio-pub > /dev/null 2>&1
+   sudo killall tokio-sub > /dev/null 2>&1
+   sudo killall tokio-timer > /dev/null 2>&1
 }
 
 function smol_cleanup() {
@@ -48,6 +79,11 @@ function smol_cleanup() {
    sudo killall smol-pong-tcp > /dev/null 2>&1
    sudo killall smol-ping-udp > /dev/null 2>&1
    sudo killall smol-pong-udp > /dev/null 2>&1
+   sudo killall smol-ping-ipc > /dev/null 2>&1
+   sudo killall smol-pong-ipc > /dev/null 2>&1
+   sudo killall smol-pub > /dev/null 2>&1
+   sudo killall smol-sub > /dev/null 2>&1
+   sudo killall smol-timer > /dev/null 2>&1
 }
 
 # kills all the processes
@@ -91,14 +127,63 @@ TCP_PING_LOCAL="127.0.0.1:9009"
 UDP_PING_REMOTE="127.0.0.1:9009"
 UDP_PING_LOCAL="127.0.0.1:9999"
 ICMP_REMOTE="127.0.0.1"
+MCAST_GROUP="239.255.0.1"
+MCAST_PING_REMOTE="$MCAST_GROUP:9009"
+MCAST_PONG_LOCAL="0.0.0.0:9009"
+MCAST_RECEIVERS=${MCAST_RECEIVERS:-3}
+SUBSCRIBERS=${SUBSCRIBERS:-3}
+CLIENTS=${CLIENTS:-8}
+CONNECTIONS=(100 1000 5000 10000)
+ACTIVE=${ACTIVE:-0.01}
+DEPTHS=(1 2 4 8 16 32 64)
+RATES=(10 100 1000 10000 100000)
+ARRIVALS=(constant poisson bursty)
+SWEEP=${SWEEP:-64:1048576}
+SLEEPS=(0.001 0.0001 0.00001)
+LOADS=(sleep spin yield channel block io)
+# Spinning tasks never give their worker back, leave the probe one
+LOAD_TASKS=${LOAD_TASKS:-1}
+PACINGS=(timer offload spin timerfd)
+# SO_BUSY_POLL budgets in microseconds, each with and without busy polling
+BUDGETS=(0 50)
+# Settings of the tokio runtime on both sides, see run-single-process.sh, the
+# LIFO slot can only be disabled by a tokio_unstable build (make unstable)
+# Executor threads of smol and async-std, worker threads of tokio
+THREAD_COUNTS=(1 2 4)
+# Cores of the pongs in the thread per core test, the pings stay on core 1
+PONG_CORES=${PONG_CORES:-0,2,3}
+# Placements of the ping threads on PING_CORES, tokio has no reactor thread
+PING_CORES=${PING_CORES:-1,2,3}
+PIN_LAYOUTS=("PIN_CPUS=1" "PIN_CPUS=2,3" "PIN_MAIN=1 PIN_CPUS=2,3" "PIN_REACTOR=1 PIN_CPUS=2,3" "PIN_REACTOR=1 PIN_MAIN=1 PIN_CPUS=2,3")
+TOKIO_CONFIGS=("TOKIO_FLAVOR=multi-thread" "TOKIO_FLAVOR=current-thread" "TOKIO_WORKERS=1" "TOKIO_WORKERS=2" "TOKIO_EVENT_INTERVAL=1" "TOKIO_EVENT_INTERVAL=256" "TOKIO_GLOBAL_QUEUE_INTERVAL=1" "TOKIO_GLOBAL_QUEUE_INTERVAL=256" "TOKIO_MAX_BLOCKING=1")
+
+
+# Pongs are dual-stack unless IPV6_ONLY=1, run-single-process.sh reads it
+IPV6_ONLY="${IPV6_ONLY:-0}"
+export IPV6_ONLY
+
+# -6 applies to every test, look for it before running any
+while getopts ":asSthP6mfnCrDRZTpbkexl" arg; do
+   if [[ ${arg} == 6 ]]
+   then
+      TCP_PING_REMOTE="[::1]:9009"
+      TCP_PING_LOCAL="[::1]:9009"
+      UDP_PING_REMOTE="[::1]:9009"
+      UDP_PING_LOCAL="[::1]:9999"
+      ICMP_REMOTE="::1"
+      plog "[ INIT ] Using IPv6 loopback"
+   fi
+done
+OPTIND=1
 
-
-
-while getopts "asSthP" arg; do
+while getopts "asSthP6mfnCrDRZTpbkexl" arg; do
    case ${arg} in
    h)
       usage
       ;;
+   6)
+      # Handled before the tests run
+      ;;
    a)
       # async std
 
@@ -144,6 +229,24 @@ while getopts "asSthP" arg; do
          cleanup
       done
 
+      #ipc
+      for i in "${INTERVALS[@]}"
+      do
+         for t in "${TASKS[@]}"
+         do
+            for k in x X
+            do
+               plog "[ START ] async_std ipc -$k with interval $i and tasks $t"
+               DURATION=$DURATION CPUS=0,1 NICE=$NICE SIZE=$SIZE TASKS=$t INTERVAL=$i  ./run-single-process.sh -${k}a
+
+               async_std_cleanup
+               plog "[ DONE ] async_std ipc -$k with interval $i and tasks $t"
+               sleep 2
+            done
+         done
+         cleanup
+      done
+
       plog "[ END ] async_std latency test"
       ;;
    S)
@@ -192,6 +295,24 @@ while getopts "asSthP" arg; do
       done
 
 
+      #ipc
+      for i in "${INTERVALS[@]}"
+      do
+         for t in "${TASKS[@]}"
+         do
+            for k in x X
+            do
+               plog "[ START ] std ipc -$k with interval $i and tasks $t"
+               DURATION=$DURATION CPUS=0,1 NICE=$NICE SIZE=$SIZE TASKS=$t INTERVAL=$i  ./run-single-process.sh -${k}S
+
+               std_cleanup
+               plog "[ DONE ] std ipc -$k with interval $i and tasks $t"
+               sleep 2
+            done
+         done
+         cleanup
+      done
+
       plog "[ END ] std latency test"
       ;;
    s)
@@ -240,6 +361,24 @@ while getopts "asSthP" arg; do
          cleanup
       done
 
+      #ipc
+      for i in "${INTERVALS[@]}"
+      do
+         for t in "${TASKS[@]}"
+         do
+            for k in x X
+            do
+               plog "[ START ] smol ipc -$k with interval $i and tasks $t"
+               DURATION=$DURATION CPUS=0,1 NICE=$NICE SIZE=$SIZE TASKS=$t INTERVAL=$i  ./run-single-process.sh -${k}s
+
+               smol_cleanup
+               plog "[ DONE ] smol ipc -$k with interval $i and tasks $t"
+               sleep 2
+            done
+         done
+         cleanup
+      done
+
       plog "[ END ] smol latency test"
       ;;
    t)
@@ -287,6 +426,24 @@ while getopts "asSthP" arg; do
          cleanup
       done
 
+      #ipc
+      for i in "${INTERVALS[@]}"
+      do
+         for t in "${TASKS[@]}"
+         do
+            for k in x X
+            do
+               plog "[ START ] tokio ipc -$k with interval $i and tasks $t"
+               DURATION=$DURATION CPUS=0,1 NICE=$NICE SIZE=$SIZE TASKS=$t INTERVAL=$i  ./run-single-process.sh -${k}t
+
+               tokio_cleanup
+               plog "[ DONE ] tokio ipc -$k with interval $i and tasks $t"
+               sleep 2
+            done
+         done
+         cleanup
+      done
+
       plog "[ END ] tokio latency test"
       ;;
    P)
@@ -306,6 +463,451 @@ while getopts "asSthP" arg; do
       done
       plog "[ END ] ping latency test"
       ;;
+   m)
+      plog "[ START ] multicast latency test"
+
+      for f in S a s t
+      do
+         for i in "${INTERVALS[@]}"
+         do
+            plog "[ START ] multicast -$f with interval $i and $MCAST_RECEIVERS receivers"
+            PONG_PIDS=()
+            for r in $(seq $MCAST_RECEIVERS)
+            do
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$MCAST_PONG_LOCAL GROUP=$MCAST_GROUP ./run-single-process.sh -M$f &
+               PONG_PIDS+=($!)
+            done
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$MCAST_PING_REMOTE RECEIVERS=$MCAST_RECEIVERS SIZE=$SIZE TASKS=0 INTERVAL=$i  ./run-single-process.sh -m$f
+
+            kill -9 ${PONG_PIDS[@]}
+            cleanup
+            plog "[ DONE ] multicast -$f with interval $i and $MCAST_RECEIVERS receivers"
+            sleep 2
+         done
+      done
+
+      plog "[ END ] multicast latency test"
+      ;;
+   f)
+      plog "[ START ] fan-out latency test"
+
+      for f in S a s t
+      do
+         for p in tcp udp
+         do
+            for i in "${INTERVALS[@]}"
+            do
+               plog "[ START ] fan-out -$f $p with interval $i and $SUBSCRIBERS subscribers"
+               DURATION=$DURATION NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL PUBSUB=$p SUBSCRIBERS=$SUBSCRIBERS INTERVAL=$i ./run-single-process.sh -b$f &
+               sleep 2
+               NICE=$NICE CPUS=1 REMOTE=$TCP_PING_REMOTE PUBSUB=$p SUBSCRIBERS=$SUBSCRIBERS SIZE=$SIZE INTERVAL=$i ./run-single-process.sh -B$f
+
+               cleanup
+               plog "[ DONE ] fan-out -$f $p with interval $i and $SUBSCRIBERS subscribers"
+               sleep 2
+            done
+         done
+      done
+
+      plog "[ END ] fan-out latency test"
+      ;;
+   n)
+      plog "[ START ] fan-in latency test"
+
+      for f in S a s t
+      do
+         for i in "${INTERVALS[@]}"
+         do
+            plog "[ START ] fan-in -$f tcp with interval $i and $CLIENTS clients"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL ./run-single-process.sh -o$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE CLIENTS=$CLIENTS SIZE=$SIZE TASKS=0 INTERVAL=$i  ./run-single-process.sh -n$f
+
+            cleanup
+            plog "[ DONE ] fan-in -$f tcp with interval $i and $CLIENTS clients"
+            sleep 2
+
+            plog "[ START ] fan-in -$f udp with interval $i and $CLIENTS clients"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE ./run-single-process.sh -O$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE CLIENTS=$CLIENTS SIZE=$SIZE TASKS=0 INTERVAL=$i  ./run-single-process.sh -N$f
+
+            cleanup
+            plog "[ DONE ] fan-in -$f udp with interval $i and $CLIENTS clients"
+            sleep 2
+         done
+      done
+
+      plog "[ END ] fan-in latency test"
+      ;;
+   C)
+      plog "[ START ] scalability latency test"
+
+      for f in S a s t
+      do
+         for c in "${CONNECTIONS[@]}"
+         do
+            plog "[ START ] scalability -$f with $c connections"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL ./run-single-process.sh -o$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE CONNECTIONS=$c ACTIVE=$ACTIVE SIZE=$SIZE TASKS=0 INTERVAL=0.01  ./run-single-process.sh -C$f
+
+            cleanup
+            plog "[ DONE ] scalability -$f with $c connections"
+            sleep 2
+         done
+      done
+
+      plog "[ END ] scalability latency test"
+      ;;
+   r)
+      plog "[ START ] connection per request test"
+      # Loopback Fast Open needs both the client and the server side enabled
+      sudo sysctl -q -w net.ipv4.tcp_fastopen=3
+
+      for f in S a s t
+      do
+         for o in 0 1
+         do
+            for i in "${INTERVALS[@]}"
+            do
+               # Latencies come from a single client, the accept rate from CLIENTS connecting back to back
+               n=1
+               if [[ $i == 0 ]]
+               then
+                  n=$CLIENTS
+               fi
+               plog "[ START ] connection per request -$f (fast open $o) with interval $i and $n clients"
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL FASTOPEN=$o ./run-single-process.sh -o$f &
+               sleep 2
+               DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE FASTOPEN=$o CLIENTS=$n SIZE=$SIZE TASKS=0 INTERVAL=$i  ./run-single-process.sh -r$f
+
+               cleanup
+               plog "[ DONE ] connection per request -$f (fast open $o) with interval $i and $n clients"
+               sleep 2
+            done
+         done
+      done
+
+      plog "[ END ] connection per request test"
+      ;;
+   D)
+      plog "[ START ] pipelining latency test"
+
+      for f in S a s t
+      do
+         for d in "${DEPTHS[@]}"
+         do
+            plog "[ START ] pipelining -$f tcp with depth $d"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL ./run-single-process.sh -o$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE DEPTH=$d SIZE=$SIZE TASKS=0 INTERVAL=0  ./run-single-process.sh -i$f
+
+            cleanup
+            plog "[ DONE ] pipelining -$f tcp with depth $d"
+            sleep 2
+
+            plog "[ START ] pipelining -$f udp with depth $d"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE ./run-single-process.sh -O$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE DEPTH=$d SIZE=$SIZE TASKS=0 INTERVAL=0  ./run-single-process.sh -I$f
+
+            cleanup
+            plog "[ DONE ] pipelining -$f udp with depth $d"
+            sleep 2
+         done
+      done
+
+      plog "[ END ] pipelining latency test"
+      ;;
+   R)
+      plog "[ START ] target rate latency test"
+
+      for f in S a s t
+      do
+         for a in "${ARRIVALS[@]}"
+         do
+            for r in "${RATES[@]}"
+            do
+               plog "[ START ] target rate -$f tcp with $a arrivals at $r msg/s"
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL ./run-single-process.sh -o$f &
+               sleep 2
+               DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE RATE=$r ARRIVAL=$a SIZE=$SIZE TASKS=0 INTERVAL=0  ./run-single-process.sh -i$f
+
+               cleanup
+               plog "[ DONE ] target rate -$f tcp with $a arrivals at $r msg/s"
+               sleep 2
+
+               plog "[ START ] target rate -$f udp with $a arrivals at $r msg/s"
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE ./run-single-process.sh -O$f &
+               sleep 2
+               DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE RATE=$r ARRIVAL=$a SIZE=$SIZE TASKS=0 INTERVAL=0  ./run-single-process.sh -I$f
+
+               cleanup
+               plog "[ DONE ] target rate -$f udp with $a arrivals at $r msg/s"
+               sleep 2
+            done
+         done
+      done
+
+      plog "[ END ] target rate latency test"
+      ;;
+   T)
+      plog "[ START ] timer lateness test"
+
+      for f in S a s t
+      do
+         for i in "${SLEEPS[@]}"
+         do
+            plog "[ START ] timer lateness -$f sleeping $i without load"
+            DURATION=$DURATION CPUS=$CPUS NICE=$NICE TASKS=0 INTERVAL=$i ./run-single-process.sh -T$f
+
+            cleanup
+            plog "[ DONE ] timer lateness -$f sleeping $i without load"
+            sleep 2
+
+            for l in "${LOADS[@]}"
+            do
+               plog "[ START ] timer lateness -$f sleeping $i next to $LOAD_TASKS $l tasks"
+               DURATION=$DURATION CPUS=$CPUS NICE=$NICE TASKS=$LOAD_TASKS LOAD=$l INTERVAL=$i ./run-single-process.sh -T$f
+
+               cleanup
+               plog "[ DONE ] timer lateness -$f sleeping $i next to $LOAD_TASKS $l tasks"
+               sleep 2
+            done
+         done
+      done
+
+      plog "[ END ] timer lateness test"
+      ;;
+   p)
+      plog "[ START ] pacing test"
+
+      for f in S a s t
+      do
+         for p in "${PACINGS[@]}"
+         do
+            for i in "${SLEEPS[@]}"
+            do
+               plog "[ START ] pacing -$f tcp with $p every $i"
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL ./run-single-process.sh -o$f &
+               sleep 2
+               DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE PACING=$p SIZE=$SIZE TASKS=0 INTERVAL=$i ./run-single-process.sh -i$f
+
+               cleanup
+               plog "[ DONE ] pacing -$f tcp with $p every $i"
+               sleep 2
+
+               plog "[ START ] pacing -$f udp with $p every $i"
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE ./run-single-process.sh -O$f &
+               sleep 2
+               DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE PACING=$p SIZE=$SIZE TASKS=0 INTERVAL=$i ./run-single-process.sh -I$f
+
+               cleanup
+               plog "[ DONE ] pacing -$f udp with $p every $i"
+               sleep 2
+
+               plog "[ START ] pacing -$f timer with $p every $i"
+               DURATION=$DURATION CPUS=$CPUS NICE=$NICE PACING=$p TASKS=0 INTERVAL=$i ./run-single-process.sh -T$f
+
+               cleanup
+               plog "[ DONE ] pacing -$f timer with $p every $i"
+               sleep 2
+            done
+         done
+      done
+
+      plog "[ END ] pacing test"
+      ;;
+   b)
+      plog "[ START ] busy polling test"
+
+      for f in S a s t
+      do
+         for b in 0 1
+         do
+            for u in "${BUDGETS[@]}"
+            do
+               for i in "${SLEEPS[@]}"
+               do
+                  plog "[ START ] busy polling -$f tcp with busy=$b so_busy_poll=$u every $i"
+                  NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL BUSY_POLL=$b SO_BUSY_POLL=$u ./run-single-process.sh -o$f &
+                  sleep 2
+                  DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE BUSY_POLL=$b SO_BUSY_POLL=$u SIZE=$SIZE TASKS=0 INTERVAL=$i ./run-single-process.sh -i$f
+
+                  cleanup
+                  plog "[ DONE ] busy polling -$f tcp with busy=$b so_busy_poll=$u every $i"
+                  sleep 2
+
+                  plog "[ START ] busy polling -$f udp with busy=$b so_busy_poll=$u every $i"
+                  NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE BUSY_POLL=$b SO_BUSY_POLL=$u ./run-single-process.sh -O$f &
+                  sleep 2
+                  DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE BUSY_POLL=$b SO_BUSY_POLL=$u SIZE=$SIZE TASKS=0 INTERVAL=$i ./run-single-process.sh -I$f
+
+                  cleanup
+                  plog "[ DONE ] busy polling -$f udp with busy=$b so_busy_poll=$u every $i"
+                  sleep 2
+               done
+            done
+         done
+      done
+
+      plog "[ END ] busy polling test"
+      ;;
+   k)
+      plog "[ START ] tokio runtime test"
+
+      for c in "${TOKIO_CONFIGS[@]}"
+      do
+         for i in "${INTERVALS[@]}"
+         do
+            plog "[ START ] tokio runtime tcp with $c every $i"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL env $c ./run-single-process.sh -ot &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE SIZE=$SIZE TASKS=0 INTERVAL=$i env $c ./run-single-process.sh -it
+
+            cleanup
+            plog "[ DONE ] tokio runtime tcp with $c every $i"
+            sleep 2
+
+            plog "[ START ] tokio runtime udp with $c every $i"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE env $c ./run-single-process.sh -Ot &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE SIZE=$SIZE TASKS=0 INTERVAL=$i env $c ./run-single-process.sh -It
+
+            cleanup
+            plog "[ DONE ] tokio runtime udp with $c every $i"
+            sleep 2
+         done
+      done
+
+      plog "[ END ] tokio runtime test"
+      ;;
+   e)
+      plog "[ START ] executor threads test"
+
+      EXECUTORS=("s SMOL_EXECUTOR=local")
+      for n in "${THREAD_COUNTS[@]}"
+      do
+         EXECUTORS+=("s THREADS=$n" "a THREADS=$n" "t TOKIO_WORKERS=$n")
+      done
+
+      for c in "${EXECUTORS[@]}"
+      do
+         read f e <<< "$c"
+         for i in "${INTERVALS[@]}"
+         do
+            plog "[ START ] executor -$f tcp with $e every $i"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL env $e ./run-single-process.sh -o$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE SIZE=$SIZE TASKS=0 INTERVAL=$i env $e ./run-single-process.sh -i$f
+
+            cleanup
+            plog "[ DONE ] executor -$f tcp with $e every $i"
+            sleep 2
+
+            plog "[ START ] executor -$f udp with $e every $i"
+            NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE env $e ./run-single-process.sh -O$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE SIZE=$SIZE TASKS=0 INTERVAL=$i env $e ./run-single-process.sh -I$f
+
+            cleanup
+            plog "[ DONE ] executor -$f udp with $e every $i"
+            sleep 2
+         done
+      done
+
+      plog "[ END ] executor threads test"
+      ;;
+   x)
+      plog "[ START ] thread per core test"
+
+      for f in S s t
+      do
+         for c in 0 1
+         do
+            for i in "${INTERVALS[@]}"
+            do
+               plog "[ START ] thread per core -$f tcp with PER_CORE=$c every $i"
+               NICE=$NICE CPUS=$PONG_CORES SIZE=$SIZE LOCAL=$TCP_PING_LOCAL PER_CORE=$c ./run-single-process.sh -o$f &
+               sleep 2
+               DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE CLIENTS=$CLIENTS PONG_PER_CORE=$c SIZE=$SIZE TASKS=0 INTERVAL=$i ./run-single-process.sh -n$f
+
+               cleanup
+               plog "[ DONE ] thread per core -$f tcp with PER_CORE=$c every $i"
+               sleep 2
+
+               plog "[ START ] thread per core -$f udp with PER_CORE=$c every $i"
+               NICE=$NICE CPUS=$PONG_CORES SIZE=$SIZE LOCAL=$UDP_PING_REMOTE PER_CORE=$c ./run-single-process.sh -O$f &
+               sleep 2
+               DURATION=$DURATION CPUS=1 NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE CLIENTS=$CLIENTS PONG_PER_CORE=$c SIZE=$SIZE TASKS=0 INTERVAL=$i ./run-single-process.sh -N$f
+
+               cleanup
+               plog "[ DONE ] thread per core -$f udp with PER_CORE=$c every $i"
+               sleep 2
+            done
+         done
+      done
+
+      plog "[ END ] thread per core test"
+      ;;
+   l)
+      plog "[ START ] thread layout test"
+
+      for f in a s t
+      do
+         for c in "${PIN_LAYOUTS[@]}"
+         do
+            if [[ $f == t && $c == *PIN_REACTOR* ]]
+            then
+               continue
+            fi
+            for i in "${INTERVALS[@]}"
+            do
+               plog "[ START ] thread layout -$f tcp with $c every $i"
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$TCP_PING_LOCAL ./run-single-process.sh -o$f &
+               sleep 2
+               DURATION=$DURATION CPUS=$PING_CORES NICE=$NICE REMOTE=$TCP_PING_REMOTE SIZE=$SIZE TASKS=0 INTERVAL=$i env $c ./run-single-process.sh -i$f
+
+               cleanup
+               plog "[ DONE ] thread layout -$f tcp with $c every $i"
+               sleep 2
+
+               plog "[ START ] thread layout -$f udp with $c every $i"
+               NICE=$NICE CPUS=0 SIZE=$SIZE LOCAL=$UDP_PING_REMOTE ./run-single-process.sh -O$f &
+               sleep 2
+               DURATION=$DURATION CPUS=$PING_CORES NICE=$NICE LOCAL=$UDP_PING_LOCAL REMOTE=$UDP_PING_REMOTE SIZE=$SIZE TASKS=0 INTERVAL=$i env $c ./run-single-process.sh -I$f
+
+               cleanup
+               plog "[ DONE ] thread layout -$f udp with $c every $i"
+               sleep 2
+            done
+         done
+      done
+
+      plog "[ END ] thread layout test"
+      ;;
+   Z)
+      plog "[ START ] payload size sweep test"
+
+      for f in S a s t
+      do
+         for i in "${INTERVALS[@]}"
+         do
+            plog "[ START ] size sweep -$f tcp with interval $i"
+            NICE=$NICE CPUS=0 SIZE=64 LOCAL=$TCP_PING_LOCAL ./run-single-process.sh -o$f &
+            sleep 2
+            DURATION=$DURATION CPUS=1 NICE=$NICE REMOTE=$TCP_PING_REMOTE SIZES=$SWEEP SIZE=64 TASKS=0 INTERVAL=$i  ./run-single-process.sh -i$f
+
+            cleanup
+            plog "[ DONE ] size sweep -$f tcp with interval $i"
+            sleep 2
+         done
+      done
+
+      plog "[ END ] payload size sweep test"
+      ;;
    *)
       usage
       ;;
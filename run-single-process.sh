@@ -8,9 +8,20 @@ plog () {
 
 usage() { printf "Usage: $0 \n\t
    -i ping tcp\n\t
-   -o pong tcp\n\t
+   -o pong tcp (IPV6_ONLY=1 for an IPv6-only socket)\n\t
    -I ping udp\n\t
-   -O pong tcp\n\t
+   -O pong udp (IPV6_ONLY=1 for an IPv6-only socket)\n\t
+   -x ping socketpair (spawns its pong)\n\t
+   -X ping pipe (spawns its pong)\n\t
+   -m ping udp multicast\n\t
+   -M pong udp multicast\n\t
+   -b publisher (PUBSUB=tcp|udp)\n\t
+   -B subscribers (PUBSUB=tcp|udp)\n\t
+   -n ping tcp fan-in (CLIENTS pings, PONG_PER_CORE=1 to a thread per core pong)\n\t
+   -N ping udp fan-in (CLIENTS pings, PONG_PER_CORE=1 to a thread per core pong)\n\t
+   -C ping tcp scalability (CONNECTIONS, ACTIVE fraction)\n\t
+   -r ping tcp with a new connection per request (FASTOPEN=1 for TCP Fast Open, CLIENTS connecting at once)\n\t
+   -T timer wake-up lateness, no networking (INTERVAL is the sleep)\n\t
    -S std\n\t
    -a async_std\n\t
    -t tokio\n\t
@@ -57,6 +68,25 @@ TOKIO_PONG_TCP="tokio-pong-tcp"
 TOKIO_PING_UDP="tokio-ping-udp"
 TOKIO_PONG_UDP="tokio-pong-udp"
 
+ASYNC_PING_IPC="async-ping-ipc"
+SMOL_PING_IPC="smol-ping-ipc"
+STD_PING_IPC="std-ping-ipc"
+TOKIO_PING_IPC="tokio-ping-ipc"
+
+ASYNC_PUB="async-pub"
+ASYNC_SUB="async-sub"
+SMOL_PUB="smol-pub"
+SMOL_SUB="smol-sub"
+STD_PUB="std-pub"
+STD_SUB="std-sub"
+TOKIO_PUB="tokio-pub"
+TOKIO_SUB="tokio-sub"
+
+ASYNC_TIMER="async-timer"
+SMOL_TIMER="smol-timer"
+STD_TIMER="std-timer"
+TOKIO_TIMER="tokio-timer"
+
 
 
 OUT_DIR="${OUT_DIR:-latency-logs}"
@@ -65,6 +95,11 @@ DURATION=${DURATION:-60}
 SIZE=${SIZE:-64}
 CPUS="${CPUS:-0,1}"
 TASKS="${TASKS:-0}"
+# What the TASKS background tasks do: sleep, spin, yield, channel, block or io
+LOAD="${LOAD:-sleep}"
+# How the pings and the timer probe wait for the next send: timer (the
+# runtime's own), offload (a blocking sleep on another thread), spin or timerfd
+PACING="${PACING:-timer}"
 NICE="${NICE:--10}"
 
 LOCAL="${LOCAL:-127.0.0.1:9009}"
@@ -72,6 +107,254 @@ REMOTE="${REMOTE:-127.0.0.1:9999}"
 
 ICMP_REMOTE="${ICMP_REMOTE:-127.0.0.1}"
 
+GROUP="${GROUP:-239.255.0.1}"
+IFACE="${IFACE:-127.0.0.1}"
+RECEIVERS="${RECEIVERS:-1}"
+
+PUBSUB="${PUBSUB:-tcp}"
+SUBSCRIBERS="${SUBSCRIBERS:-1}"
+
+CLIENTS="${CLIENTS:-4}"
+
+CONNECTIONS="${CONNECTIONS:-1000}"
+ACTIVE="${ACTIVE:-0.01}"
+
+# Requests kept in flight by the tcp, udp and ipc pings, 0 is one at a time
+DEPTH="${DEPTH:-0}"
+if [[ $DEPTH -gt 0 ]]
+then
+   DEPTH_FLAG="--depth $DEPTH"
+   DEPTH_COL=",depth"
+else
+   DEPTH_FLAG=""
+   DEPTH_COL=",intended,arrival,pacing"
+fi
+
+# Target rate in msg/s with absolute send deadlines instead of INTERVAL sleeps,
+# ARRIVAL is constant, poisson or bursty (ON and OFF seconds)
+RATE="${RATE:-}"
+ARRIVAL="${ARRIVAL:-constant}"
+ON="${ON:-0.1}"
+OFF="${OFF:-0.9}"
+if [[ -n $RATE ]]
+then
+   RATE_FLAG="--rate $RATE --arrival $ARRIVAL --on $ON --off $OFF"
+else
+   RATE_FLAG=""
+fi
+
+# Replays a recorded trace (see read_trace in the tcp and udp pings) against
+# the pong instead of SIZE/INTERVAL messages, SPEED scales its timing
+TRACE="${TRACE:-}"
+SPEED="${SPEED:-1}"
+if [[ -n $TRACE ]]
+then
+   TRACE_FLAG="--trace $TRACE --speed $SPEED"
+   TRACE_COL=",connection"
+else
+   TRACE_FLAG=""
+   TRACE_COL=""
+fi
+
+# Sizes swept by the tcp ping on a single connection, a list (64,1024) or a
+# geometric range (64:1048576[:factor]), STEP seconds each after WARMUP exchanges
+SIZES="${SIZES:-}"
+STEP="${STEP:-5}"
+WARMUP="${WARMUP:-100}"
+if [[ -n $SIZES ]]
+then
+   SIZES_FLAG="--sizes $SIZES --step $STEP --warmup $WARMUP"
+else
+   SIZES_FLAG=""
+fi
+
+# Length-prefixed tcp messages, set it for both the pong and the ping
+FRAMED="${FRAMED:-0}"
+if [[ $FRAMED == 1 ]]
+then
+   FRAMED_FLAG="--framed"
+else
+   FRAMED_FLAG=""
+fi
+
+# Busy polling of the tcp and udp sockets instead of parking on the reactor
+# (BUSY_POLL=1) and SO_BUSY_POLL in microseconds, set them for both sides
+BUSY_POLL="${BUSY_POLL:-0}"
+SO_BUSY_POLL="${SO_BUSY_POLL:-0}"
+POLL_FLAG="--so-busy-poll $SO_BUSY_POLL"
+if [[ $BUSY_POLL == 1 ]]
+then
+   POLL_FLAG="--busy-poll $POLL_FLAG"
+fi
+if [[ $DEPTH -eq 0 && -z $TRACE && -z $SIZES ]]
+then
+   POLL_COL=",poll"
+else
+   POLL_COL=""
+fi
+
+# What the tcp pong sends back: echo, fixed (REPLY bytes), sink (nothing) or
+# source (a stream of REPLY byte messages), the ping expects the same
+MODE="${MODE:-echo}"
+REPLY="${REPLY:-}"
+MODE_FLAG="--mode $MODE"
+if [[ -n $REPLY ]]
+then
+   MODE_FLAG="$MODE_FLAG --reply $REPLY"
+fi
+if [[ $MODE == sink || $MODE == source ]]
+then
+   TCP_COL=",mode"
+else
+   TCP_COL="$DEPTH_COL$POLL_COL$TRACE_COL"
+fi
+
+# Service time of the tcp and udp pongs before they answer: WORK is none, spin,
+# block, offload or timer, SERVICE the mean in microseconds and DISTRIBUTION
+# fixed, exponential or lognormal (spread by SIGMA)
+WORK="${WORK:-none}"
+SERVICE="${SERVICE:-0}"
+DISTRIBUTION="${DISTRIBUTION:-fixed}"
+SIGMA="${SIGMA:-1}"
+WORK_FLAG="--work $WORK --service $SERVICE --distribution $DISTRIBUTION --sigma $SIGMA"
+
+# NOISE extra tcp connections from the ping, echoing NOISE_SIZE byte messages
+# at NOISE_RATE msg/s each (back to back if unset) with the pong, or only
+# sending them to NOISE_ADDRESS when NOISE_SINK=1 (a pong in sink mode)
+NOISE="${NOISE:-0}"
+NOISE_FLAG="--noise $NOISE"
+if [[ -n $NOISE_ADDRESS ]]
+then
+   NOISE_FLAG="$NOISE_FLAG --noise-address $NOISE_ADDRESS"
+fi
+if [[ -n $NOISE_SIZE ]]
+then
+   NOISE_FLAG="$NOISE_FLAG --noise-size $NOISE_SIZE"
+fi
+if [[ -n $NOISE_RATE ]]
+then
+   NOISE_FLAG="$NOISE_FLAG --noise-rate $NOISE_RATE"
+fi
+if [[ $NOISE_SINK == 1 ]]
+then
+   NOISE_FLAG="$NOISE_FLAG --noise-sink"
+fi
+
+# TCP Fast Open on loopback needs net.ipv4.tcp_fastopen=3
+FASTOPEN="${FASTOPEN:-0}"
+if [[ $FASTOPEN == 1 ]]
+then
+   TFO_FLAG="--fast-open"
+   TFO_NAME="tcp-tfo"
+else
+   TFO_FLAG=""
+   TFO_NAME="tcp"
+fi
+
+# IPv6 pongs are dual-stack unless IPV6_ONLY=1
+IPV6_ONLY="${IPV6_ONLY:-0}"
+if [[ $IPV6_ONLY == 1 ]]
+then
+   V6_FLAG="--ipv6-only"
+else
+   V6_FLAG=""
+fi
+
+# Thread per core pongs: a single-threaded runtime with a SO_REUSEPORT socket
+# of its own on each core of CPUS, tokio current-thread, smol local or a std
+# thread. async-std has no such runtime and ignores it.
+PER_CORE="${PER_CORE:-0}"
+if [[ $PER_CORE == 1 ]]
+then
+   PER_CORE_FLAG="--per-core"
+   TOKIO_FLAVOR="${TOKIO_FLAVOR:-current-thread}"
+   SMOL_EXECUTOR="${SMOL_EXECUTOR:-local}"
+else
+   PER_CORE_FLAG=""
+fi
+
+# The pong a fan-in talks to, PONG_PER_CORE=1 on the pings when it runs thread
+# per core: the layout goes in the log name, for parse.py to tell them apart
+if [[ $PONG_PER_CORE == 1 ]]
+then
+   PONG_TAG="-per-core"
+else
+   PONG_TAG=""
+fi
+
+# The tokio runtime: TOKIO_FLAVOR is multi-thread or current-thread, the others
+# are left to tokio's defaults when unset, TOKIO_LIFO=0 needs a build with
+# RUSTFLAGS="--cfg tokio_unstable" (make unstable)
+TOKIO_FLAVOR="${TOKIO_FLAVOR:-multi-thread}"
+TOKIO_FLAG="--flavor $TOKIO_FLAVOR"
+if [[ -n $TOKIO_WORKERS ]]
+then
+   TOKIO_FLAG="$TOKIO_FLAG --worker-threads $TOKIO_WORKERS"
+fi
+if [[ -n $TOKIO_EVENT_INTERVAL ]]
+then
+   TOKIO_FLAG="$TOKIO_FLAG --event-interval $TOKIO_EVENT_INTERVAL"
+fi
+if [[ -n $TOKIO_GLOBAL_QUEUE_INTERVAL ]]
+then
+   TOKIO_FLAG="$TOKIO_FLAG --global-queue-interval $TOKIO_GLOBAL_QUEUE_INTERVAL"
+fi
+if [[ -n $TOKIO_MAX_BLOCKING ]]
+then
+   TOKIO_FLAG="$TOKIO_FLAG --max-blocking-threads $TOKIO_MAX_BLOCKING"
+fi
+if [[ $TOKIO_LIFO == 0 ]]
+then
+   TOKIO_FLAG="$TOKIO_FLAG --disable-lifo-slot"
+fi
+
+# Threads of the smol and async-std executors, left to their defaults when
+# unset (one for smol, one per core for async-std), SMOL_EXECUTOR is shared or
+# local (the main thread only, THREADS must be unset)
+SMOL_EXECUTOR="${SMOL_EXECUTOR:-shared}"
+SMOL_FLAG="--executor $SMOL_EXECUTOR"
+ASYNC_FLAG=""
+if [[ -n $THREADS ]]
+then
+   SMOL_FLAG="$SMOL_FLAG --threads $THREADS"
+   ASYNC_FLAG="--threads $THREADS"
+fi
+
+# Threads placed by the binaries themselves, on top of taskset: PIN_CPUS the
+# runtime workers round robin (0,2-3), PIN_MAIN the main thread and
+# PIN_REACTOR the reactor thread of smol and async-std. The mapping of each run
+# goes to a log next to the results.
+PIN_FLAG=""
+if [[ -n $PIN_CPUS ]]
+then
+   PIN_FLAG="$PIN_FLAG --cpus $PIN_CPUS"
+fi
+if [[ -n $PIN_MAIN ]]
+then
+   PIN_FLAG="$PIN_FLAG --main-cpu $PIN_MAIN"
+fi
+TOKIO_FLAG="$TOKIO_FLAG $PIN_FLAG"
+if [[ -n $PIN_REACTOR ]]
+then
+   PIN_FLAG="$PIN_FLAG --reactor-cpu $PIN_REACTOR"
+fi
+SMOL_FLAG="$SMOL_FLAG $PIN_FLAG"
+ASYNC_FLAG="$ASYNC_FLAG $PIN_FLAG"
+if [[ -n $PIN_CPUS$PIN_MAIN$PIN_REACTOR ]]
+then
+   PIN_LOG="$OUT_DIR/cpus-$TS.log"
+else
+   PIN_LOG="/dev/null"
+fi
+
+# ICMP has no socket address to tell the family from
+if [[ $ICMP_REMOTE == *:* ]]
+then
+   ICMP_FAMILY="ipv6"
+else
+   ICMP_FAMILY="ipv4"
+fi
+
 
 mkdir -p $OUT_DIR
 
@@ -80,13 +363,24 @@ mkdir -p $OUT_DIR
 # - 2 = Pong TCP
 # - 3 = Ping UDP
 # - 4 = Pong UDP
+# - 5 = Ping socketpair
+# - 6 = Ping pipe
+# - 7 = Ping UDP multicast
+# - 8 = Pong UDP multicast
+# - 9 = Publisher
+# - 10 = Subscribers
+# - 11 = Ping TCP fan-in
+# - 12 = Ping UDP fan-in
+# - 13 = Ping TCP scalability
+# - 14 = Ping TCP connection per request
+# - 15 = Timer lateness
 TORUN=1
 
 
 plog "[ INIT ] Duration will be $DURATION seconds"
 plog "[ INIT ] Sending a message each $INTERVAL"
 plog "[ INIT ] Message size $SIZE bytes"
-while getopts "iIoOsSathP" arg; do
+while getopts "iIoOxXmMbBnNCrTsSathP" arg; do
    case ${arg} in
    h)
       usage
@@ -115,33 +409,176 @@ while getopts "iIoOsSathP" arg; do
       plog "[ INIT ] Running the pong udp"
       TORUN=4
       ;;
+   x)
+      # Start ping socketpair
+
+      plog "[ INIT ] Running the ping socketpair"
+      TORUN=5
+      ;;
+   X)
+      # Start ping pipe
+
+      plog "[ INIT ] Running the ping pipe"
+      TORUN=6
+      ;;
+   m)
+      # Start ping udp multicast
+
+      plog "[ INIT ] Running the ping udp multicast"
+      TORUN=7
+      ;;
+   M)
+      # Start pong udp multicast
+
+      plog "[ INIT ] Running the pong udp multicast"
+      TORUN=8
+      ;;
+   b)
+      # Start publisher
+
+      plog "[ INIT ] Running the publisher $PUBSUB"
+      TORUN=9
+      ;;
+   B)
+      # Start subscribers
+
+      plog "[ INIT ] Running $SUBSCRIBERS subscribers $PUBSUB"
+      TORUN=10
+      ;;
+   n)
+      # Start ping tcp fan-in
+
+      plog "[ INIT ] Running $CLIENTS pings tcp"
+      TORUN=11
+      ;;
+   N)
+      # Start ping udp fan-in
+
+      plog "[ INIT ] Running $CLIENTS pings udp"
+      TORUN=12
+      ;;
+   C)
+      # Start ping tcp scalability
+
+      plog "[ INIT ] Running the ping tcp over $CONNECTIONS connections"
+      TORUN=13
+      ;;
+   r)
+      # Start ping tcp with a connection per request
+
+      plog "[ INIT ] Running the ping $TFO_NAME with a connection per request"
+      TORUN=14
+      ;;
+   T)
+      # Start the timer lateness probe
+
+      plog "[ INIT ] Running the timer lateness probe"
+      TORUN=15
+      ;;
    S)
       # std
       case ${TORUN} in
       1)
          LOG_FILE="$OUT_DIR/std-ping-tcp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${TCP_COL}" > $LOG_FILE
          plog "[ RUN ] Running std ping tcp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_TCP $REMOTE $SIZE $INTERVAL -w -c -s $TASKS -d $DURATION >> $LOG_FILE 2> /dev/null
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_TCP $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG $SIZES_FLAG $FRAMED_FLAG $MODE_FLAG $NOISE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG -d $DURATION >> $LOG_FILE 2> /dev/null
          plog "[ DONE ] Running std ping tcp"
          ;;
       2)
+         LOG_FILE="$OUT_DIR/std-pong-tcp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running std pong tcp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PONG_TCP $LOCAL $SIZE > /dev/null 2>&1
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PONG_TCP $LOCAL $SIZE $V6_FLAG $TFO_FLAG $FRAMED_FLAG $MODE_FLAG $WORK_FLAG $POLL_FLAG $PER_CORE_FLAG -c >> $LOG_FILE 2> /dev/null
          plog "[ DONE ] Running std pong tcp"
          ;;
       3)
          LOG_FILE="$OUT_DIR/std-ping-udp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}${POLL_COL}${TRACE_COL}" > $LOG_FILE
          plog "[ RUN ] Running std ping udp"
-         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w -c -s $TASKS >> $LOG_FILE 2> /dev/null
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG >> $LOG_FILE 2> /dev/null
          plog "[ DONE ] Running std ping udp"
          ;;
       4)
+         LOG_FILE="$OUT_DIR/std-pong-udp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running std pong udp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PONG_UDP $LOCAL $SIZE > /dev/null 2>&1
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PONG_UDP $LOCAL $SIZE $V6_FLAG $WORK_FLAG $POLL_FLAG $PER_CORE_FLAG -c >> $LOG_FILE 2> /dev/null
          plog "[ DONE ] Running std pong udp"
          ;;
+      5)
+         LOG_FILE="$OUT_DIR/std-ping-unix-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running std ping socketpair"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_IPC socketpair $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std ping socketpair"
+         ;;
+      6)
+         LOG_FILE="$OUT_DIR/std-ping-pipe-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running std ping pipe"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_IPC pipe $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std ping pipe"
+         ;;
+      7)
+         LOG_FILE="$OUT_DIR/std-ping-multicast-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,pacing" > $LOG_FILE
+         plog "[ RUN ] Running std ping udp multicast"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --multicast --interface $IFACE --receivers $RECEIVERS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std ping udp multicast"
+         ;;
+      8)
+         plog "[ RUN ] Running std pong udp multicast"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PONG_UDP $LOCAL $SIZE --join $GROUP --interface $IFACE > /dev/null 2>&1
+         plog "[ DONE ] Running std pong udp multicast"
+         ;;
+      9)
+         plog "[ RUN ] Running std publisher $PUBSUB"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PUB $PUBSUB $LOCAL $SIZE $INTERVAL -n $SUBSCRIBERS -d $DURATION > /dev/null 2>&1
+         plog "[ DONE ] Running std publisher $PUBSUB"
+         ;;
+      10)
+         LOG_FILE="$OUT_DIR/std-sub-$PUBSUB-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,subscribers" > $LOG_FILE
+         plog "[ RUN ] Running std subscribers $PUBSUB"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_SUB $PUBSUB $REMOTE $SIZE -n $SUBSCRIBERS -c >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std subscribers $PUBSUB"
+         ;;
+      11)
+         LOG_FILE="$OUT_DIR/std-ping-tcp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running std $CLIENTS pings tcp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_TCP $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std $CLIENTS pings tcp"
+         ;;
+      12)
+         LOG_FILE="$OUT_DIR/std-ping-udp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running std $CLIENTS pings udp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std $CLIENTS pings udp"
+         ;;
+      13)
+         LOG_FILE="$OUT_DIR/std-ping-tcp-scale-$TS-$CONNECTIONS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running std ping tcp over $CONNECTIONS connections"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_TCP $REMOTE $SIZE $INTERVAL --connections $CONNECTIONS --active $ACTIVE -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std ping tcp over $CONNECTIONS connections"
+         ;;
+      14)
+         LOG_FILE="$OUT_DIR/std-ping-$TFO_NAME-reconnect-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing,client" > $LOG_FILE
+         plog "[ RUN ] Running std ping $TFO_NAME with a connection per request"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_PING_TCP $REMOTE $SIZE $INTERVAL --reconnect --clients $CLIENTS $TFO_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std ping $TFO_NAME with a connection per request"
+         ;;
+      15)
+         LOG_FILE="$OUT_DIR/std-timer-$TS-$TASKS-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing" > $LOG_FILE
+         plog "[ RUN ] Running std timer lateness"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$STD_TIMER $INTERVAL -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION >> $LOG_FILE 2> /dev/null
+         plog "[ DONE ] Running std timer lateness"
+         ;;
       *)
          usage
          ;;
@@ -153,28 +590,105 @@ while getopts "iIoOsSathP" arg; do
       case ${TORUN} in
       1)
          LOG_FILE="$OUT_DIR/async_std-ping-tcp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${TCP_COL}" > $LOG_FILE
          plog "[ RUN ] Running async_std ping tcp"
-         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_TCP $REMOTE $SIZE $INTERVAL -w -c -s $TASKS -d $DURATION >> $LOG_FILE 2> /dev/null
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_TCP $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG $SIZES_FLAG $FRAMED_FLAG $MODE_FLAG $NOISE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running async_std ping tcp"
          ;;
       2)
+         LOG_FILE="$OUT_DIR/async-pong-tcp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running async_std pong tcp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PONG_TCP $LOCAL $SIZE > /dev/null 2>&1
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PONG_TCP $LOCAL $SIZE $V6_FLAG $TFO_FLAG $FRAMED_FLAG $MODE_FLAG $WORK_FLAG $POLL_FLAG $ASYNC_FLAG -c >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running async_std pong tcp"
          ;;
       3)
          LOG_FILE="$OUT_DIR/async_std-ping-udp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}${POLL_COL}${TRACE_COL}" > $LOG_FILE
          plog "[ RUN ] Running async_std ping udp"
-         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w -c -s $TASKS -d $DURATION >> $LOG_FILE 2> /dev/null
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running async_std ping udp"
          ;;
       4)
+         LOG_FILE="$OUT_DIR/async-pong-udp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running async_std pong udp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PONG_UDP $LOCAL $SIZE > /dev/null 2>&1
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PONG_UDP $LOCAL $SIZE $V6_FLAG $WORK_FLAG $POLL_FLAG $ASYNC_FLAG -c >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running async_std pong udp"
          ;;
+      5)
+         LOG_FILE="$OUT_DIR/async_std-ping-unix-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running async_std ping socketpair"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_IPC socketpair $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std ping socketpair"
+         ;;
+      6)
+         LOG_FILE="$OUT_DIR/async_std-ping-pipe-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running async_std ping pipe"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_IPC pipe $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std ping pipe"
+         ;;
+      7)
+         LOG_FILE="$OUT_DIR/async_std-ping-multicast-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,pacing" > $LOG_FILE
+         plog "[ RUN ] Running async_std ping udp multicast"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --multicast --interface $IFACE --receivers $RECEIVERS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std ping udp multicast"
+         ;;
+      8)
+         plog "[ RUN ] Running async_std pong udp multicast"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PONG_UDP $LOCAL $SIZE --join $GROUP --interface $IFACE $ASYNC_FLAG > /dev/null 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std pong udp multicast"
+         ;;
+      9)
+         plog "[ RUN ] Running async_std publisher $PUBSUB"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PUB $PUBSUB $LOCAL $SIZE $INTERVAL -n $SUBSCRIBERS -d $DURATION $ASYNC_FLAG > /dev/null 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std publisher $PUBSUB"
+         ;;
+      10)
+         LOG_FILE="$OUT_DIR/async_std-sub-$PUBSUB-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,subscribers" > $LOG_FILE
+         plog "[ RUN ] Running async_std subscribers $PUBSUB"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_SUB $PUBSUB $REMOTE $SIZE -n $SUBSCRIBERS -c $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std subscribers $PUBSUB"
+         ;;
+      11)
+         LOG_FILE="$OUT_DIR/async_std-ping-tcp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running async_std $CLIENTS pings tcp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_TCP $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std $CLIENTS pings tcp"
+         ;;
+      12)
+         LOG_FILE="$OUT_DIR/async_std-ping-udp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running async_std $CLIENTS pings udp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std $CLIENTS pings udp"
+         ;;
+      13)
+         LOG_FILE="$OUT_DIR/async_std-ping-tcp-scale-$TS-$CONNECTIONS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running async_std ping tcp over $CONNECTIONS connections"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_TCP $REMOTE $SIZE $INTERVAL --connections $CONNECTIONS --active $ACTIVE -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std ping tcp over $CONNECTIONS connections"
+         ;;
+      14)
+         LOG_FILE="$OUT_DIR/async_std-ping-$TFO_NAME-reconnect-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing,client" > $LOG_FILE
+         plog "[ RUN ] Running async_std ping $TFO_NAME with a connection per request"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_PING_TCP $REMOTE $SIZE $INTERVAL --reconnect --clients $CLIENTS $TFO_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std ping $TFO_NAME with a connection per request"
+         ;;
+      15)
+         LOG_FILE="$OUT_DIR/async_std-timer-$TS-$TASKS-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing" > $LOG_FILE
+         plog "[ RUN ] Running async_std timer lateness"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$ASYNC_TIMER $INTERVAL -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $ASYNC_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running async_std timer lateness"
+         ;;
       *)
          usage
          ;;
@@ -186,28 +700,105 @@ while getopts "iIoOsSathP" arg; do
       case ${TORUN} in
       1)
          LOG_FILE="$OUT_DIR/tokio-ping-tcp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${TCP_COL}" > $LOG_FILE
          plog "[ RUN ] Running tokio ping tcp"
-         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_TCP $REMOTE $SIZE $INTERVAL -w -c -s $TASKS -d $DURATION >> $LOG_FILE 2> /dev/null
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_TCP $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG $SIZES_FLAG $FRAMED_FLAG $MODE_FLAG $NOISE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running tokio ping tcp"
          ;;
       2)
+         LOG_FILE="$OUT_DIR/tokio-pong-tcp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running tokio pong tcp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PONG_TCP $LOCAL $SIZE > /dev/null 2>&1
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PONG_TCP $LOCAL $SIZE $V6_FLAG $TFO_FLAG $FRAMED_FLAG $MODE_FLAG $WORK_FLAG $POLL_FLAG $TOKIO_FLAG $PER_CORE_FLAG -c >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running tokio pong tcp"
          ;;
       3)
          LOG_FILE="$OUT_DIR/tokio-ping-udp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}${POLL_COL}${TRACE_COL}" > $LOG_FILE
          plog "[ RUN ] Running tokio ping udp"
-         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w -c -s $TASKS -d $DURATION >> $LOG_FILE 2> /dev/null
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running tokio ping udp"
          ;;
       4)
+         LOG_FILE="$OUT_DIR/tokio-pong-udp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running tokio pong udp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PONG_UDP $LOCAL $SIZE > /dev/null 2>&1
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PONG_UDP $LOCAL $SIZE $V6_FLAG $WORK_FLAG $POLL_FLAG $TOKIO_FLAG $PER_CORE_FLAG -c >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running tokio pong udp"
          ;;
+      5)
+         LOG_FILE="$OUT_DIR/tokio-ping-unix-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running tokio ping socketpair"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_IPC socketpair $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio ping socketpair"
+         ;;
+      6)
+         LOG_FILE="$OUT_DIR/tokio-ping-pipe-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running tokio ping pipe"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_IPC pipe $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio ping pipe"
+         ;;
+      7)
+         LOG_FILE="$OUT_DIR/tokio-ping-multicast-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,pacing" > $LOG_FILE
+         plog "[ RUN ] Running tokio ping udp multicast"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --multicast --interface $IFACE --receivers $RECEIVERS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio ping udp multicast"
+         ;;
+      8)
+         plog "[ RUN ] Running tokio pong udp multicast"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PONG_UDP $LOCAL $SIZE --join $GROUP --interface $IFACE $TOKIO_FLAG > /dev/null 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio pong udp multicast"
+         ;;
+      9)
+         plog "[ RUN ] Running tokio publisher $PUBSUB"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PUB $PUBSUB $LOCAL $SIZE $INTERVAL -n $SUBSCRIBERS -d $DURATION $TOKIO_FLAG > /dev/null 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio publisher $PUBSUB"
+         ;;
+      10)
+         LOG_FILE="$OUT_DIR/tokio-sub-$PUBSUB-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,subscribers" > $LOG_FILE
+         plog "[ RUN ] Running tokio subscribers $PUBSUB"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_SUB $PUBSUB $REMOTE $SIZE -n $SUBSCRIBERS -c $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio subscribers $PUBSUB"
+         ;;
+      11)
+         LOG_FILE="$OUT_DIR/tokio-ping-tcp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running tokio $CLIENTS pings tcp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_TCP $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio $CLIENTS pings tcp"
+         ;;
+      12)
+         LOG_FILE="$OUT_DIR/tokio-ping-udp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running tokio $CLIENTS pings udp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio $CLIENTS pings udp"
+         ;;
+      13)
+         LOG_FILE="$OUT_DIR/tokio-ping-tcp-scale-$TS-$CONNECTIONS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running tokio ping tcp over $CONNECTIONS connections"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_TCP $REMOTE $SIZE $INTERVAL --connections $CONNECTIONS --active $ACTIVE -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio ping tcp over $CONNECTIONS connections"
+         ;;
+      14)
+         LOG_FILE="$OUT_DIR/tokio-ping-$TFO_NAME-reconnect-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing,client" > $LOG_FILE
+         plog "[ RUN ] Running tokio ping $TFO_NAME with a connection per request"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_PING_TCP $REMOTE $SIZE $INTERVAL --reconnect --clients $CLIENTS $TFO_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio ping $TFO_NAME with a connection per request"
+         ;;
+      15)
+         LOG_FILE="$OUT_DIR/tokio-timer-$TS-$TASKS-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing" > $LOG_FILE
+         plog "[ RUN ] Running tokio timer lateness"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$TOKIO_TIMER $INTERVAL -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $TOKIO_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running tokio timer lateness"
+         ;;
       *)
          usage
          ;;
@@ -218,28 +809,105 @@ while getopts "iIoOsSathP" arg; do
       case ${TORUN} in
       1)
          LOG_FILE="$OUT_DIR/smol-ping-tcp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${TCP_COL}" > $LOG_FILE
          plog "[ RUN ] Running smol ping tcp"
-         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_TCP $REMOTE $SIZE $INTERVAL -w -c -s $TASKS -d $DURATION >> $LOG_FILE 2> /dev/null
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_TCP $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG $SIZES_FLAG $FRAMED_FLAG $MODE_FLAG $NOISE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running smol ping tcp"
          ;;
       2)
+         LOG_FILE="$OUT_DIR/smol-pong-tcp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running smol pong tcp"
-         nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PONG_TCP $LOCAL $SIZE > /dev/null 2>&1
+         nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PONG_TCP $LOCAL $SIZE $V6_FLAG $TFO_FLAG $FRAMED_FLAG $MODE_FLAG $WORK_FLAG $POLL_FLAG $SMOL_FLAG $PER_CORE_FLAG -c >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running smol pong tcp"
          ;;
       3)
          LOG_FILE="$OUT_DIR/smol-ping-udp-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-         echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}${POLL_COL}${TRACE_COL}" > $LOG_FILE
          plog "[ RUN ] Running smol ping udp"
-         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w -c -s $TASKS -d $DURATION >> $LOG_FILE 2> /dev/null
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG $TRACE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING $POLL_FLAG -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running smol ping udp"
          ;;
       4)
+         LOG_FILE="$OUT_DIR/smol-pong-udp-$TS-$SIZE.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer" > $LOG_FILE
          plog "[ RUN ] Running smol pong udp"
-         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PONG_UDP $LOCAL $SIZE > /dev/null 2>&1
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PONG_UDP $LOCAL $SIZE $V6_FLAG $WORK_FLAG $POLL_FLAG $SMOL_FLAG $PER_CORE_FLAG -c >> $LOG_FILE 2>> $PIN_LOG
          plog "[ DONE ] Running smol pong udp"
          ;;
+      5)
+         LOG_FILE="$OUT_DIR/smol-ping-unix-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running smol ping socketpair"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_IPC socketpair $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol ping socketpair"
+         ;;
+      6)
+         LOG_FILE="$OUT_DIR/smol-ping-pipe-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family${DEPTH_COL}" > $LOG_FILE
+         plog "[ RUN ] Running smol ping pipe"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_IPC pipe $SIZE $INTERVAL -w $DEPTH_FLAG $RATE_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol ping pipe"
+         ;;
+      7)
+         LOG_FILE="$OUT_DIR/smol-ping-multicast-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,pacing" > $LOG_FILE
+         plog "[ RUN ] Running smol ping udp multicast"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --multicast --interface $IFACE --receivers $RECEIVERS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol ping udp multicast"
+         ;;
+      8)
+         plog "[ RUN ] Running smol pong udp multicast"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PONG_UDP $LOCAL $SIZE --join $GROUP --interface $IFACE $SMOL_FLAG > /dev/null 2>> $PIN_LOG
+         plog "[ DONE ] Running smol pong udp multicast"
+         ;;
+      9)
+         plog "[ RUN ] Running smol publisher $PUBSUB"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PUB $PUBSUB $LOCAL $SIZE $INTERVAL -n $SUBSCRIBERS -d $DURATION $SMOL_FLAG > /dev/null 2>> $PIN_LOG
+         plog "[ DONE ] Running smol publisher $PUBSUB"
+         ;;
+      10)
+         LOG_FILE="$OUT_DIR/smol-sub-$PUBSUB-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,peer,subscribers" > $LOG_FILE
+         plog "[ RUN ] Running smol subscribers $PUBSUB"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_SUB $PUBSUB $REMOTE $SIZE -n $SUBSCRIBERS -c $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol subscribers $PUBSUB"
+         ;;
+      11)
+         LOG_FILE="$OUT_DIR/smol-ping-tcp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running smol $CLIENTS pings tcp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_TCP $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol $CLIENTS pings tcp"
+         ;;
+      12)
+         LOG_FILE="$OUT_DIR/smol-ping-udp-fanin$PONG_TAG-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running smol $CLIENTS pings udp"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_UDP $LOCAL $REMOTE $SIZE $INTERVAL --clients $CLIENTS -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol $CLIENTS pings udp"
+         ;;
+      13)
+         LOG_FILE="$OUT_DIR/smol-ping-tcp-scale-$TS-$CONNECTIONS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,client,connections,pacing" > $LOG_FILE
+         plog "[ RUN ] Running smol ping tcp over $CONNECTIONS connections"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_TCP $REMOTE $SIZE $INTERVAL --connections $CONNECTIONS --active $ACTIVE -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol ping tcp over $CONNECTIONS connections"
+         ;;
+      14)
+         LOG_FILE="$OUT_DIR/smol-ping-$TFO_NAME-reconnect-$TS-$TASKS-$SIZE-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing,client" > $LOG_FILE
+         plog "[ RUN ] Running smol ping $TFO_NAME with a connection per request"
+         sudo  nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_PING_TCP $REMOTE $SIZE $INTERVAL --reconnect --clients $CLIENTS $TFO_FLAG -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol ping $TFO_NAME with a connection per request"
+         ;;
+      15)
+         LOG_FILE="$OUT_DIR/smol-timer-$TS-$TASKS-$INTERVAL.csv"
+         echo "framework,transport,test,count,rate,payload,tasks,value,unit,family,pacing" > $LOG_FILE
+         plog "[ RUN ] Running smol timer lateness"
+         sudo nice -n $NICE taskset -c $CPUS $BIN_DIR/$SMOL_TIMER $INTERVAL -c -s $TASKS --load $LOAD --pacing $PACING -d $DURATION $SMOL_FLAG >> $LOG_FILE 2>> $PIN_LOG
+         plog "[ DONE ] Running smol timer lateness"
+         ;;
       *)
          usage
          ;;
@@ -248,8 +916,8 @@ while getopts "iIoOsSathP" arg; do
    P)
       plog "[ RUN ] ICMP ping"
       LOG_FILE="$OUT_DIR/icmp-ping-$TS-$TASKS-$SIZE-$INTERVAL.csv"
-      echo "framework,transport,test,count,rate,payload,tasks,value,unit" > $LOG_FILE
-      sudo timeout $DURATION nice -n $NICE taskset -c $CPUS ping $ICMP_REMOTE -i $INTERVAL | awk -v intv=$INTERVAL 'BEGIN {FS="[=]|[ ]"} NR>=2 {printf("ping,icmp,rtt,%d,%f,64,0,%s,%s\n",$6,intv,$10,$11)}' >> $LOG_FILE 2> /dev/null
+      echo "framework,transport,test,count,rate,payload,tasks,value,unit,family" > $LOG_FILE
+      sudo timeout $DURATION nice -n $NICE taskset -c $CPUS ping $ICMP_REMOTE -i $INTERVAL | awk -v intv=$INTERVAL -v fam=$ICMP_FAMILY 'BEGIN {FS="[=]|[ ]"} NR>=2 {printf("ping,icmp,rtt,%d,%f,64,0,%s,%s,%s\n",$6,intv,$10,$11,fam)}' >> $LOG_FILE 2> /dev/null
       plog "[ DONE ] ICMP ping"
       ;;
    *)
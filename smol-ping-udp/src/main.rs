@@ -1,11 +1,24 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use smol::lock::Mutex;
 use smol::net::UdpSocket;
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use smol::prelude::*;
+use smol::{Async, Timer};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::mem::ManuallyDrop;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::*;
 use std::sync::Arc;
+use std::sync::OnceLock;
+use std::thread;
 use std::time::{Duration, Instant};
 
 const MAX_SAMPLES: usize = 100_000_000;
@@ -20,10 +33,598 @@ struct Args {
     wait: bool,
     #[clap(short, long, default_value = "0")]
     spawn: usize,
+    #[clap(long, value_enum, default_value = "sleep")]
+    load: Load,
+    #[clap(long, value_enum, default_value = "timer")]
+    pacing: Pacing,
+    #[clap(long)]
+    busy_poll: bool,
+    #[clap(long, default_value = "0")]
+    so_busy_poll: u32,
     #[clap(short, long)]
     csv: bool,
     #[clap(short, long, default_value = "60")]
     duration: u64,
+    #[clap(long)]
+    multicast: bool,
+    #[clap(long, default_value = "0.0.0.0")]
+    interface: Ipv4Addr,
+    #[clap(long, default_value = "1")]
+    receivers: usize,
+    #[clap(long, default_value = "1000")]
+    timeout: u64,
+    #[clap(long, default_value = "1")]
+    clients: usize,
+    #[clap(long, default_value = "0")]
+    depth: usize,
+    #[clap(long, value_parser = parse_positive)]
+    rate: Option<f64>,
+    #[clap(long, value_enum, default_value = "constant")]
+    arrival: Arrival,
+    #[clap(long, default_value = "0.1", value_parser = parse_positive)]
+    on: f64,
+    #[clap(long, default_value = "0.9", value_parser = parse_positive)]
+    off: f64,
+    #[clap(long)]
+    trace: Option<PathBuf>,
+    #[clap(long, default_value = "1.0", value_parser = parse_speed)]
+    speed: f64,
+    #[clap(long, value_enum, default_value = "shared")]
+    executor: ExecutorKind,
+    #[clap(long, default_value = "1", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: usize,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// Where the tasks run: an executor shared by `threads` threads, like smol's
+// global one, or a local one run by the main thread next to the test.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExecutorKind {
+    Shared,
+    Local,
+}
+
+// How the tasks are executed. It goes in the framework column, plain smol for
+// the shared executor on one thread that smol::spawn would use.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    kind: ExecutorKind,
+    threads: usize,
+    pinning: Pinning,
+}
+
+static EXECUTOR: smol::Executor<'static> = smol::Executor::new();
+static KIND: OnceLock<ExecutorKind> = OnceLock::new();
+
+thread_local! {
+    static LOCAL: smol::LocalExecutor<'static> = const { smol::LocalExecutor::new() };
+}
+
+// smol::spawn on the executor picked by --executor
+fn spawn<T: Send + 'static>(
+    future: impl std::future::Future<Output = T> + Send + 'static,
+) -> smol::Task<T> {
+    match KIND.get() {
+        Some(ExecutorKind::Local) => LOCAL.with(|local| local.spawn(future)),
+        _ => EXECUTOR.spawn(future),
+    }
+}
+
+impl RuntimeConfig {
+    // Starts the executor threads and blocks the main thread on `future`
+    fn block_on<T>(&self, future: impl std::future::Future<Output = T>) -> T {
+        KIND.set(self.kind).unwrap();
+        // async-io starts it on the first socket or timer, the test starts
+        // once it is in place
+        if let Some(core) = self.pinning.reactor {
+            let watcher = pin_named("async-io", 1, move |_| core);
+            smol::block_on(smol::Timer::after(Duration::from_millis(1)));
+            watcher.join().unwrap();
+        }
+        match self.kind {
+            ExecutorKind::Shared => {
+                for n in 1..=self.threads {
+                    let core = self.pinning.worker(n - 1);
+                    std::thread::Builder::new()
+                        .name(format!("smol-{}", n))
+                        .spawn(move || {
+                            if let Some(core) = core {
+                                pin(core).unwrap();
+                            }
+                            smol::block_on(EXECUTOR.run(smol::future::pending::<()>()))
+                        })
+                        .unwrap();
+                }
+                // Last, for the executor threads not to inherit it
+                self.pinning.pin_main();
+                smol::block_on(future)
+            }
+            ExecutorKind::Local => {
+                self.pinning.pin_main();
+                LOCAL.with(|local| smol::block_on(local.run(future)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "smol")?;
+        if let ExecutorKind::Local = self.kind {
+            write!(f, "/local")?;
+        }
+        if self.threads != 1 {
+            write!(f, "/threads={}", self.threads)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// Background tasks competing with the test for the runtime: sleeping, spinning
+// without ever yielding, yielding in a tight loop, passing tokens around a ring
+// of channels, blocking the worker thread, or doing socket I/O of their own.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Load {
+    Sleep,
+    Spin,
+    Yield,
+    Channel,
+    Block,
+    Io,
+}
+
+impl Load {
+    fn name(&self) -> &'static str {
+        match self {
+            Load::Sleep => "sleep",
+            Load::Spin => "spin",
+            Load::Yield => "yield",
+            Load::Channel => "channel",
+            Load::Block => "block",
+            Load::Io => "io",
+        }
+    }
+}
+
+// The tasks column, the load profile and how many tasks run it
+#[derive(Clone, Copy, Debug)]
+struct Tasks {
+    load: Load,
+    count: usize,
+}
+
+impl fmt::Display for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.load.name(), self.count)
+    }
+}
+
+// How the socket waits for data: parked on the runtime's reactor or busy
+// polled, and the SO_BUSY_POLL budget in microseconds, 0 is off.
+#[derive(Clone, Copy, Debug)]
+struct Poll {
+    busy: bool,
+    budget: u32,
+}
+
+impl fmt::Display for Poll {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let wait = if self.busy { "busy" } else { "reactor" };
+        write!(f, "{}:{}", wait, self.budget)
+    }
+}
+
+// How the pings wait for their next send: the runtime's own timer, a blocking
+// sleep handed to another thread, spinning on the clock, or a timerfd armed with
+// the absolute deadline and read like any other file descriptor.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Pacing {
+    Timer,
+    Offload,
+    Spin,
+    Timerfd,
+}
+
+impl Pacing {
+    fn name(&self) -> &'static str {
+        match self {
+            Pacing::Timer => "timer",
+            Pacing::Offload => "offload",
+            Pacing::Spin => "spin",
+            Pacing::Timerfd => "timerfd",
+        }
+    }
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+fn timerfd(flags: libc::c_int) -> std::io::Result<OwnedFd> {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags | libc::TFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Arms the timerfd for an absolute deadline, Instant is CLOCK_MONOTONIC on Linux.
+// False if the deadline has passed already, a zero expiry would disarm it.
+fn arm(fd: RawFd, deadline: Instant) -> std::io::Result<bool> {
+    let wait = deadline.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        return Ok(false);
+    }
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let nanos = now.tv_nsec as u64 + wait.subsec_nanos() as u64;
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: now.tv_sec + (wait.as_secs() + nanos / 1_000_000_000) as libc::time_t,
+            tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+        },
+    };
+    let res =
+        unsafe { libc::timerfd_settime(fd, libc::TFD_TIMER_ABSTIME, &spec, std::ptr::null_mut()) };
+    if res < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+// How many times the timerfd expired since it was armed
+fn expirations(fd: RawFd) -> std::io::Result<u64> {
+    let mut count = [0u8; 8];
+    let n = unsafe { libc::read(fd, count.as_mut_ptr() as *mut libc::c_void, count.len()) };
+    if n < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(u64::from_ne_bytes(count))
+}
+
+// Waits with the chosen pacing, the timerfd is created once and armed again
+// for every deadline.
+struct Pacer {
+    pacing: Pacing,
+    timerfd: Option<Async<OwnedFd>>,
+}
+
+impl Pacer {
+    fn new(pacing: Pacing) -> std::io::Result<Pacer> {
+        let timerfd = match pacing {
+            Pacing::Timerfd => Some(Async::new(timerfd(libc::TFD_NONBLOCK)?)?),
+            _ => None,
+        };
+        Ok(Pacer { pacing, timerfd })
+    }
+
+    async fn sleep(&self, wait: Duration) {
+        self.until(Instant::now() + wait).await;
+    }
+
+    async fn until(&self, deadline: Instant) {
+        match self.pacing {
+            Pacing::Timer => {
+                Timer::at(deadline).await;
+            }
+            Pacing::Offload => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                smol::unblock(move || std::thread::sleep(wait)).await;
+            }
+            Pacing::Spin => {
+                while Instant::now() < deadline {
+                    std::hint::spin_loop();
+                }
+            }
+            Pacing::Timerfd => {
+                let timerfd = self.timerfd.as_ref().unwrap();
+                if arm(timerfd.as_raw_fd(), deadline).unwrap() {
+                    timerfd
+                        .read_with(|fd| expirations(fd.as_raw_fd()))
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
+
+// SO_BUSY_POLL: blocking reads and epoll waits on the socket poll the device
+// queue for up to `micros` before going to sleep.
+fn set_busy_poll(socket: &impl AsRawFd, micros: u32) -> std::io::Result<()> {
+    let value = micros as libc::c_int;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BUSY_POLL,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// CPU time of the whole process, every thread included
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+// The std socket behind an async one, for non-blocking calls on it
+fn borrow_std<T: FromRawFd>(socket: &impl AsRawFd) -> ManuallyDrop<T> {
+    ManuallyDrop::new(unsafe { T::from_raw_fd(socket.as_raw_fd()) })
+}
+
+// Busy polling retries a non-blocking call until it goes through, yielding in
+// between so that the runtime never parks in epoll waiting for the socket.
+async fn spin<R>(mut op: impl FnMut() -> std::io::Result<R>) -> std::io::Result<R> {
+    loop {
+        match op() {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => smol::future::yield_now().await,
+            res => return res,
+        }
+    }
+}
+
+async fn spin_read_exact(socket: &std::net::UdpSocket, buffer: &mut [u8]) -> std::io::Result<()> {
+    let mut read: usize = 0;
+    while read < buffer.len() {
+        read += spin(|| socket.recv(&mut buffer[read..])).await?;
+    }
+    Ok(())
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Arrival {
+    Constant,
+    Poisson,
+    Bursty,
+}
+
+impl Arrival {
+    fn name(&self) -> &'static str {
+        match self {
+            Arrival::Constant => "constant",
+            Arrival::Poisson => "poisson",
+            Arrival::Bursty => "bursty",
+        }
+    }
+}
+
+// Send deadlines as offsets from the start of the test. They are absolute, so
+// a late exchange does not push back the messages after it. Each send still
+// waits for the previous reply, once the round trip outgrows the gap the
+// achieved rate falls short of the target. Bursty arrivals send at the target
+// rate during the on periods and nothing during the off ones.
+struct Schedule {
+    arrival: Arrival,
+    gap: f64,
+    on: f64,
+    off: f64,
+    next: f64,
+    rng: StdRng,
+}
+
+impl Schedule {
+    fn new(arrival: Arrival, rate: f64, on: f64, off: f64) -> Schedule {
+        Schedule {
+            arrival,
+            gap: 1.0 / rate,
+            on,
+            off,
+            next: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        let at = self.next;
+        self.next += match self.arrival {
+            Arrival::Constant | Arrival::Bursty => self.gap,
+            // Exponential inter-arrival times
+            Arrival::Poisson => -(1.0 - self.rng.gen::<f64>()).ln() * self.gap,
+        };
+        if let Arrival::Bursty = self.arrival {
+            let period = self.on + self.off;
+            let phase = self.next % period;
+            if phase >= self.on {
+                self.next += period - phase;
+            }
+        }
+        Duration::from_secs_f64(at)
+    }
+}
+
+// Rates and times in seconds, which have to be positive
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok(x),
+        Ok(_) => Err(format!("{}: has to be positive", value)),
+        Err(e) => Err(format!("{:?}: {}", value, e)),
+    }
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+// Jain's fairness index: 1 when every client got the same share, 1/n when a
+// single one got everything.
+fn jain(shares: &[f64]) -> f64 {
+    let sum: f64 = shares.iter().sum();
+    let squares: f64 = shares.iter().map(|x| x * x).sum();
+    if squares == 0.0 {
+        return 0.0;
+    }
+    sum * sum / (shares.len() as f64 * squares)
+}
+
+// Replies come back unicast from each receiver, so the socket is not
+// connected to the group.
+fn bind_multicast(
+    address: SocketAddr,
+    group: SocketAddr,
+    interface: Ipv4Addr,
+) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    match group {
+        SocketAddr::V4(_) => {
+            socket.set_multicast_if_v4(&interface)?;
+            socket.set_multicast_loop_v4(true)?;
+        }
+        SocketAddr::V6(_) => socket.set_multicast_loop_v6(true)?,
+    }
+    socket.bind(&address.into())?;
+    Ok(socket.into())
 }
 
 async fn read_exact(
@@ -38,65 +639,670 @@ async fn read_exact(
     Ok(())
 }
 
+// One message of a recorded trace
+struct Message {
+    offset: Duration,
+    size: usize,
+    connection: u64,
+}
+
+// Replays run faster or slower by a positive factor
+fn parse_speed(speed: &str) -> Result<f64, String> {
+    match speed.parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(factor),
+        Ok(_) => Err(format!("{}: the speed has to be a positive factor", speed)),
+        Err(e) => Err(format!("{:?}: {}", speed, e)),
+    }
+}
+
+// Traces ending in .csv hold one `offset,size[,connection]` line per message,
+// with the offset in seconds, lines that do not parse (headers) are skipped.
+// Any other trace is binary, 16 bytes per message: little-endian u64 offset in
+// ns, u32 size and u32 connection. Offsets are divided by speed.
+fn read_trace(path: &Path, speed: f64) -> std::io::Result<Vec<Message>> {
+    let data = fs::read(path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let mut trace = Vec::new();
+    if path.extension().is_some_and(|e| e == "csv") {
+        for (n, line) in String::from_utf8_lossy(&data).lines().enumerate() {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            let offset = fields[0].parse::<f64>();
+            let size = fields.get(1).map(|s| s.parse::<usize>());
+            if let (Ok(offset), Some(Ok(size))) = (offset, size) {
+                // Negative, NaN or overflowing offsets are no time to send at
+                let offset = Duration::try_from_secs_f64(offset / speed).map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{}:{}: offset {}: {}", path.display(), n + 1, fields[0], e),
+                    )
+                })?;
+                trace.push(Message {
+                    offset,
+                    size,
+                    connection: fields.get(2).and_then(|c| c.parse().ok()).unwrap_or(0),
+                });
+            }
+        }
+    } else {
+        for record in data.chunks_exact(16) {
+            let mut offset = [0u8; 8];
+            let mut size = [0u8; 4];
+            let mut connection = [0u8; 4];
+            offset.copy_from_slice(&record[0..8]);
+            size.copy_from_slice(&record[8..12]);
+            connection.copy_from_slice(&record[12..16]);
+            trace.push(Message {
+                offset: Duration::from_nanos(u64::from_le_bytes(offset)).div_f64(speed),
+                size: u32::from_le_bytes(size) as usize,
+                connection: u32::from_le_bytes(connection) as u64,
+            });
+        }
+    }
+    trace.sort_by_key(|m| m.offset);
+    Ok(trace)
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run_wait(
     address: SocketAddr,
     remote: SocketAddr,
     size: usize,
     interval: f64,
     csv: bool,
-    tasks: usize,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    poll: Poll,
+    mut schedule: Option<Schedule>,
     flag: Arc<AtomicBool>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(remote);
     let socket = Arc::new(UdpSocket::bind(address).await?);
     socket.connect(remote).await?;
+    if poll.budget > 0 {
+        set_busy_poll(socket.as_ref(), poll.budget)?;
+    }
+    let busy = poll
+        .busy
+        .then(|| borrow_std::<std::net::UdpSocket>(socket.as_ref()));
 
     let mut count: u64 = 0;
     let mut payload = vec![0u8; size];
 
     let mut samples = Vec::with_capacity(MAX_SAMPLES);
+    // With a target rate the rate column holds the mean interval between sends
+    let interval = schedule.as_ref().map_or(interval, |s| s.gap);
+    let arrival = schedule.as_ref().map_or("interval", |s| s.arrival.name());
+    let cpu = cpu_time();
+    let start = Instant::now();
 
+    let pacer = Pacer::new(pacing)?;
     while flag.load(Relaxed) {
+        let deadline = match schedule.as_mut() {
+            Some(schedule) => {
+                let deadline = start + schedule.next();
+                pacer.until(deadline).await;
+                deadline
+            }
+            None => Instant::now(),
+        };
         let count_bytes: [u8; 8] = count.to_le_bytes();
         payload[0..8].copy_from_slice(&count_bytes);
         let now = Instant::now();
 
-        socket.send(&payload).await.unwrap();
-        read_exact(&socket, &mut payload).await.unwrap();
+        if let Some(socket) = busy.as_deref() {
+            spin(|| socket.send(&payload)).await.unwrap();
+            spin_read_exact(socket, &mut payload).await.unwrap();
+        } else {
+            socket.send(&payload).await.unwrap();
+            read_exact(&socket, &mut payload).await.unwrap();
+        }
+
+        let done = Instant::now();
+        let elapsed = done - now;
+        // On a --rate schedule, a send that leaves after its deadline waited on
+        // the previous replies and the response time counts that wait too
+        // (coordinated omission). Interval pacing has no deadline to miss.
+        samples.push((count, elapsed, deadline - start, done - deadline));
+
+        if schedule.is_none() {
+            pacer.sleep(Duration::from_secs_f64(interval)).await;
+        }
+        count = count.wrapping_add(1);
+    }
+    let achieved = count as f64 / start.elapsed().as_secs_f64();
+    let usage = (cpu_time() - cpu).as_secs_f64() / start.elapsed().as_secs_f64();
+
+    for (count, s, intended, r) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, poll
+            println!(
+                "{},udp,rtt,{},{},{},{},{},ns,{},{},{},{},{}",
+                runtime,
+                count,
+                interval,
+                payload.len(),
+                tasks,
+                s.as_nanos(),
+                family,
+                intended.as_nanos(),
+                arrival,
+                pacing.name(),
+                poll
+            );
+            // Interval pacing sends once the previous reply is back, the
+            // response time would only repeat the round trip
+            if schedule.is_some() {
+                println!(
+                    "{},udp,response,{},{},{},{},{},ns,{},{},{},{},{}",
+                    runtime,
+                    count,
+                    interval,
+                    payload.len(),
+                    tasks,
+                    r.as_nanos(),
+                    family,
+                    intended.as_nanos(),
+                    arrival,
+                    pacing.name(),
+                    poll
+                );
+            }
+        } else {
+            println!(
+                "{} bytes: seq={} time={:?} response={:?}",
+                payload.len(),
+                count,
+                s,
+                r
+            );
+        }
+    }
+
+    // Late replies hold back the sends after them, the achieved rate shows
+    // whether the loop kept up with the target
+    if schedule.is_some() {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, poll
+            println!(
+                "{},udp,achieved,{},{},{},{},{:.1},msg/s,{},,{},{},{}",
+                runtime,
+                count,
+                interval,
+                payload.len(),
+                tasks,
+                achieved,
+                family,
+                arrival,
+                pacing.name(),
+                poll
+            );
+        } else {
+            println!("achieved={:.1} msg/s", achieved);
+        }
+    }
 
-        let elapsed = now.elapsed();
-        samples.push(elapsed);
+    // Busy polling buys its latency with CPU time, all of the process counts
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, poll
+        println!(
+            "{},udp,cpu,{},{},{},{},{:.1},%,{},,{},{},{}",
+            runtime,
+            count,
+            interval,
+            payload.len(),
+            tasks,
+            usage * 100.0,
+            family,
+            arrival,
+            pacing.name(),
+            poll
+        );
+    } else {
+        println!("cpu={:.1}%", usage * 100.0);
+    }
+    Ok(())
+}
+
+// Replays a trace, one socket per connection id of the trace. Messages leave at
+// their offset from the start of the replay and wait for their echo, one held
+// back by the previous reply shows up in the response time.
+#[allow(clippy::too_many_arguments)]
+async fn run_trace(
+    address: SocketAddr,
+    remote: SocketAddr,
+    size: usize,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    trace: Vec<Message>,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(remote);
+    // The rate column holds the mean interval between messages
+    let span = trace.last().map_or(0.0, |m| m.offset.as_secs_f64());
+    let interval = span / trace.len().max(1) as f64;
+    let mut connections = BTreeMap::<u64, Vec<Message>>::new();
+    for message in trace {
+        connections
+            .entry(message.connection)
+            .or_default()
+            .push(message);
+    }
 
-        smol::unblock(move || std::thread::sleep(Duration::from_secs_f64(interval))).await;
+    let mut streams = Vec::with_capacity(connections.len());
+    for (connection, messages) in connections {
+        // Each connection needs its own port for the replies to find it
+        let socket = Arc::new(UdpSocket::bind(SocketAddr::new(address.ip(), 0)).await?);
+        socket.connect(remote).await?;
+        streams.push((connection, socket, messages));
+    }
+
+    let start = Instant::now();
+    let handles: Vec<_> = streams
+        .into_iter()
+        .map(|(connection, socket, messages)| {
+            let c_flag = flag.clone();
+            spawn(async move {
+                // Messages larger than the pong buffer go out as several datagrams
+                let bytes = |m: &Message| m.size.max(1);
+                let largest = messages.iter().map(bytes).max().unwrap_or(0);
+                let mut payload = vec![0u8; largest];
+                let mut samples = Vec::with_capacity(messages.len());
+                let pacer = Pacer::new(pacing).unwrap();
+                for (count, message) in messages.iter().enumerate() {
+                    if !c_flag.load(Relaxed) {
+                        break;
+                    }
+                    let deadline = start + message.offset;
+                    pacer.until(deadline).await;
+                    let bytes = bytes(message);
+                    let now = Instant::now();
+
+                    for chunk in payload[..bytes].chunks(size) {
+                        socket.send(chunk).await.unwrap();
+                    }
+                    read_exact(&socket, &mut payload[..bytes]).await.unwrap();
+
+                    let done = Instant::now();
+                    samples.push((
+                        connection,
+                        count,
+                        bytes,
+                        done - now,
+                        deadline - start,
+                        done - deadline,
+                    ));
+                }
+                samples
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        for (connection, count, bytes, s, intended, r) in handle.await {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing, connection
+                println!(
+                    "{},udp,rtt,{},{},{},{},{},ns,{},{},trace,{},{}",
+                    runtime,
+                    count,
+                    interval,
+                    bytes,
+                    tasks,
+                    s.as_nanos(),
+                    family,
+                    intended.as_nanos(),
+                    pacing.name(),
+                    connection
+                );
+                println!(
+                    "{},udp,response,{},{},{},{},{},ns,{},{},trace,{},{}",
+                    runtime,
+                    count,
+                    interval,
+                    bytes,
+                    tasks,
+                    r.as_nanos(),
+                    family,
+                    intended.as_nanos(),
+                    pacing.name(),
+                    connection
+                );
+            } else {
+                println!(
+                    "{} bytes: connection={} seq={} time={:?} response={:?}",
+                    bytes, connection, count, s, r
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_multicast(
+    address: SocketAddr,
+    group: SocketAddr,
+    interface: Ipv4Addr,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    receivers: usize,
+    timeout: Duration,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(group);
+    let socket = UdpSocket::from(Async::new(bind_multicast(address, group, interface)?)?);
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+    let mut reply = vec![0u8; size];
+    let mut replies = HashMap::<SocketAddr, u64>::new();
+
+    let mut samples = Vec::with_capacity(MAX_SAMPLES);
+    let pacer = Pacer::new(pacing)?;
+    while flag.load(Relaxed) {
+        let count_bytes: [u8; 8] = count.to_le_bytes();
+        payload[0..8].copy_from_slice(&count_bytes);
+        let now = Instant::now();
+
+        socket.send_to(&payload, group).await.unwrap();
+        let mut received = 0;
+        while received < receivers {
+            let left = timeout.saturating_sub(now.elapsed());
+            // Whoever did not reply in time lost this message
+            let recv = async { Some(socket.recv_from(&mut reply).await) };
+            let expire = async {
+                Timer::after(left).await;
+                None
+            };
+            let (_, peer) = match recv.or(expire).await {
+                Some(res) => res.unwrap(),
+                None => break,
+            };
+            let elapsed = now.elapsed();
+            // Late reply to a previous message
+            if reply[0..8] != count_bytes {
+                continue;
+            }
+            *replies.entry(peer).or_default() += 1;
+            samples.push((count, peer, elapsed));
+            received += 1;
+        }
+
+        pacer.sleep(Duration::from_secs_f64(interval)).await;
         count = count.wrapping_add(1);
     }
 
-    for s in samples {
+    for (c, peer, s) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, pacing
+            println!(
+                "{},multicast,rtt,{},{},{},{},{},ns,{},{},{}",
+                runtime,
+                c,
+                interval,
+                payload.len(),
+                tasks,
+                s.as_nanos(),
+                family,
+                peer,
+                pacing.name()
+            );
+        } else {
+            println!(
+                "{} bytes from {}: seq={} time={:?}",
+                payload.len(),
+                peer,
+                c,
+                s
+            );
+        }
+    }
+
+    // Delivery ratio of every receiver, and of the group as a whole. Receivers
+    // that never replied have no address, they show up as none.
+    let ratio = |n: u64, sent: u64| {
+        if sent == 0 {
+            0.0
+        } else {
+            n as f64 / sent as f64
+        }
+    };
+    let total: u64 = replies.values().sum();
+    let mut delivery: Vec<(String, f64)> = replies
+        .iter()
+        .map(|(peer, n)| (peer.to_string(), ratio(*n, count)))
+        .collect();
+    for _ in replies.len()..receivers {
+        delivery.push(("none".to_string(), 0.0));
+    }
+    delivery.push(("all".to_string(), ratio(total, count * receivers as u64)));
+    for (peer, ratio) in delivery {
         if csv {
-            // framework, transport, test, count, rate, payload, tasks, value, unit
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, pacing
             println!(
-                "smol,udp,rtt,{},{},{},{},{},ns",
+                "{},multicast,delivery,{},{},{},{},{},ratio,{},{},{}",
+                runtime,
                 count,
                 interval,
                 payload.len(),
                 tasks,
-                s.as_nanos()
+                ratio,
+                family,
+                peer,
+                pacing.name()
+            );
+        } else {
+            println!("{}: delivery={:.4}", peer, ratio);
+        }
+    }
+    Ok(())
+}
+
+// Every client connects before any of them starts, so that they all compete
+// for the pong over the whole test.
+#[allow(clippy::too_many_arguments)]
+async fn run_fan_in(
+    address: SocketAddr,
+    remote: SocketAddr,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    clients: usize,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(remote);
+    let mut streams = Vec::with_capacity(clients);
+    for _ in 0..clients {
+        // Each client needs its own port for the replies to find it
+        let socket = Arc::new(UdpSocket::bind(SocketAddr::new(address.ip(), 0)).await?);
+        socket.connect(remote).await?;
+        streams.push(socket);
+    }
+
+    let handles: Vec<_> = streams
+        .into_iter()
+        .map(|socket| {
+            let c_flag = flag.clone();
+            spawn(async move {
+                let mut count: u64 = 0;
+                let mut payload = vec![0u8; size];
+                let mut samples = Vec::with_capacity(MAX_SAMPLES / clients);
+                let pacer = Pacer::new(pacing).unwrap();
+                while c_flag.load(Relaxed) {
+                    let count_bytes: [u8; 8] = count.to_le_bytes();
+                    payload[0..8].copy_from_slice(&count_bytes);
+                    let now = Instant::now();
+
+                    socket.send(&payload).await.unwrap();
+                    read_exact(&socket, &mut payload).await.unwrap();
+                    samples.push((count, now.elapsed()));
+
+                    pacer.sleep(Duration::from_secs_f64(interval)).await;
+                    count = count.wrapping_add(1);
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut shares = Vec::with_capacity(clients);
+    for (client, handle) in handles.into_iter().enumerate() {
+        let samples = handle.await;
+        shares.push(samples.len() as f64);
+        for (c, s) in samples {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, client, connections, pacing
+                println!(
+                    "{},udp,rtt,{},{},{},{},{},ns,{},{},{},{}",
+                    runtime,
+                    c,
+                    interval,
+                    size,
+                    tasks,
+                    s.as_nanos(),
+                    family,
+                    client,
+                    clients,
+                    pacing.name()
+                );
+            } else {
+                println!("{} bytes: client={} seq={} time={:?}", size, client, c, s);
+            }
+        }
+    }
+
+    // How evenly the pong shared its round trips among the clients
+    let fairness = jain(&shares);
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, client, connections, pacing
+        println!(
+            "{},udp,fairness,{},{},{},{},{},jain,{},all,{},{}",
+            runtime,
+            clients,
+            interval,
+            size,
+            tasks,
+            fairness,
+            family,
+            clients,
+            pacing.name()
+        );
+    } else {
+        println!("fairness: jain={:.4}", fairness);
+    }
+    Ok(())
+}
+
+// Keeps `depth` requests in flight: a new one leaves as soon as a reply comes
+// back, so the pipeline paces itself and the interval is not used.
+#[allow(clippy::too_many_arguments)]
+async fn run_depth(
+    address: SocketAddr,
+    remote: SocketAddr,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    depth: usize,
+    timeout: Duration,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(remote);
+    let socket = UdpSocket::bind(address).await?;
+    socket.connect(remote).await?;
+    let mut pending = HashMap::<u64, Instant>::with_capacity(depth);
+    let mut count: u64 = 0;
+    let mut lost: u64 = 0;
+    let mut payload = vec![0u8; size];
+    let mut count_bytes = [0u8; 8];
+    let mut samples = Vec::with_capacity(MAX_SAMPLES);
+
+    while flag.load(Relaxed) || !pending.is_empty() {
+        // Top the pipeline up until the test is over, then drain it
+        while flag.load(Relaxed) && pending.len() < depth {
+            payload[0..8].copy_from_slice(&count.to_le_bytes());
+            pending.insert(count, Instant::now());
+            socket.send(&payload).await.unwrap();
+            count = count.wrapping_add(1);
+        }
+
+        let recv = socket.recv(&mut payload);
+        let expire = async {
+            Timer::after(timeout).await;
+            Err(ErrorKind::TimedOut.into())
+        };
+        let reply = recv.or(expire).await;
+        match reply {
+            Ok(_) => {}
+            // Whatever is still in flight is lost, start over
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                lost += pending.len() as u64;
+                pending.clear();
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        count_bytes.copy_from_slice(&payload[0..8]);
+        let c = u64::from_le_bytes(count_bytes);
+        if let Some(instant) = pending.remove(&c) {
+            samples.push((c, instant.elapsed()));
+        }
+    }
+
+    for (c, s) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+            println!(
+                "{},udp,rtt,{},{},{},{},{},ns,{},{}",
+                runtime,
+                c,
+                interval,
+                size,
+                tasks,
+                s.as_nanos(),
+                family,
+                depth
             );
         } else {
-            println!("{} bytes: seq={} time={:?}", payload.len(), count, s);
+            println!("{} bytes: seq={} depth={} time={:?}", size, c, depth, s);
         }
     }
 
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+        println!(
+            "{},udp,lost,{},{},{},{},{},msgs,{},{}",
+            runtime, count, interval, size, tasks, lost, family, depth
+        );
+    } else {
+        println!("lost={}", lost);
+    }
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run(
     address: SocketAddr,
     remote: SocketAddr,
     size: usize,
     interval: f64,
     csv: bool,
-    tasks: usize,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let family = family(remote);
     let socket = Arc::new(UdpSocket::bind(address).await?);
     socket.connect(remote).await?;
 
@@ -104,7 +1310,7 @@ async fn run(
 
     let c_socket = socket.clone();
     let c_pending = pending.clone();
-    smol::spawn(async move {
+    spawn(async move {
         let mut payload = vec![0u8; size];
         let mut count_bytes = [0u8; 8];
         loop {
@@ -114,14 +1320,17 @@ async fn run(
 
             let instant = c_pending.lock().await.remove(&count).unwrap();
             if csv {
-                // framework, transport, test, count, rate, payload, tasks, value, unit
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, pacing
                 println!(
-                    "smol,udp,rtt,{},{},{},{},{},ns",
+                    "{},udp,rtt,{},{},{},{},{},ns,{},{}",
+                    runtime,
                     count,
                     interval,
                     payload.len(),
                     tasks,
-                    instant.elapsed().as_nanos()
+                    instant.elapsed().as_nanos(),
+                    family,
+                    pacing.name()
                 );
             } else {
                 println!(
@@ -137,6 +1346,7 @@ async fn run(
 
     //Perform RUN tests
     let mut count: u64 = 0;
+    let pacer = Pacer::new(pacing)?;
     loop {
         let mut payload = vec![0u8; size];
         let count_bytes: [u8; 8] = count.to_le_bytes();
@@ -145,58 +1355,247 @@ async fn run(
         pending.lock().await.insert(count, Instant::now());
         socket.send(&payload).await.unwrap();
 
-        smol::unblock(move || std::thread::sleep(Duration::from_secs_f64(interval))).await;
+        pacer.sleep(Duration::from_secs_f64(interval)).await;
         count = count.wrapping_add(1);
     }
 }
 
-fn main() {
-    let args = Args::parse();
-    let flag = Arc::new(AtomicBool::new(true));
+async fn background(load: Load) {
+    let mut x: usize = 1;
+    match load {
+        Load::Sleep => loop {
+            x = x.wrapping_mul(2);
+            smol::unblock(move || std::thread::sleep(Duration::from_millis(1))).await;
+        },
+        // Never yields, so it keeps a worker for itself
+        Load::Spin => loop {
+            x = std::hint::black_box(x.wrapping_mul(2));
+        },
+        Load::Yield => loop {
+            smol::future::yield_now().await;
+        },
+        Load::Block => loop {
+            std::thread::sleep(Duration::from_millis(1));
+            smol::future::yield_now().await;
+        },
+        Load::Io => {
+            let socket = smol::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.connect(socket.local_addr().unwrap()).await.unwrap();
+            let mut buf = [0u8; 64];
+            loop {
+                socket.send(&buf).await.unwrap();
+                socket.recv(&mut buf).await.unwrap();
+                // The datagram is always there already, which is no reason
+                // to keep the worker
+                smol::future::yield_now().await;
+            }
+        }
+        Load::Channel => unreachable!("Channel tasks are spawned as a ring"),
+    }
+}
 
-    smol::block_on(async {
-        for _ in 0..args.spawn {
-            smol::spawn(async move {
-                let mut x: usize = 1;
-                loop {
-                    x = x.wrapping_mul(2);
-                    smol::unblock(move || std::thread::sleep(Duration::from_millis(1))).await;
+fn spawn_load(load: Load, count: usize) {
+    if let Load::Channel = load {
+        // Every task forwards the tokens it gets to the next one
+        let (txs, rxs): (Vec<_>, Vec<_>) =
+            (0..count).map(|_| smol::channel::bounded::<u64>(1)).unzip();
+        for tx in &txs {
+            tx.try_send(0).unwrap();
+        }
+        for (i, rx) in rxs.into_iter().enumerate() {
+            let tx = txs[(i + 1) % count].clone();
+            spawn(async move {
+                while let Ok(token) = rx.recv().await {
+                    if tx.send(token).await.is_err() {
+                        break;
+                    }
                 }
             })
             .detach();
         }
+        return;
+    }
+    for _ in 0..count {
+        spawn(background(load)).detach();
+    }
+}
 
-        if !args.wait {
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    // Only the request/reply loop follows a target rate, and busy polls
+    let request_reply =
+        args.trace.is_none() && !args.multicast && args.depth == 0 && args.clients == 1;
+    if args.rate.is_some() && !request_reply {
+        return Err(conflict(
+            "--rate does not combine with --trace, --multicast, --clients or --depth",
+        ));
+    }
+    if (args.busy_poll || args.so_busy_poll > 0)
+        && !(request_reply && (args.wait || args.rate.is_some()))
+    {
+        return Err(conflict(
+            "Busy polling needs -w or --rate, and no --trace, --multicast, --clients or --depth",
+        ));
+    }
+    let shared = matches!(args.executor, ExecutorKind::Shared);
+    // The local executor runs on the main thread
+    if !shared && args.threads != 1 {
+        return Err(conflict(
+            "--threads needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    if !shared && args.cpus.is_some() {
+        return Err(conflict(
+            "--cpus needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+    // A trace that does not read is refused like any other bad argument
+    let trace = args.trace.as_ref().map(|path| {
+        read_trace(path, args.speed).unwrap_or_else(|e| {
+            Args::command()
+                .error(clap::ErrorKind::InvalidValue, e)
+                .exit()
+        })
+    });
+    let tasks = Tasks {
+        load: args.load,
+        count: args.spawn,
+    };
+    let poll = Poll {
+        busy: args.busy_poll,
+        budget: args.so_busy_poll,
+    };
+    let flag = Arc::new(AtomicBool::new(true));
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        kind: args.executor,
+        threads: args.threads,
+        pinning,
+    };
+
+    runtime.block_on(async {
+        spawn_load(args.load, args.spawn);
+
+        if !args.wait
+            && args.rate.is_none()
+            && args.trace.is_none()
+            && !args.multicast
+            && args.depth == 0
+            && args.clients == 1
+        {
             run(
                 args.address,
                 args.remote,
                 args.size,
                 args.interval,
                 args.csv,
-                args.spawn,
+                tasks,
+                runtime,
+                args.pacing,
             )
             .await
             .unwrap();
         }
 
-        let c_duration = args.duration.clone();
+        let c_duration = args.duration;
         let c_flag = flag.clone();
-        smol::spawn(async move {
+        spawn(async move {
             smol::unblock(move || std::thread::sleep(Duration::from_secs(c_duration))).await;
             c_flag.store(false, Relaxed);
         })
         .detach();
 
-        run_wait(
-            args.address,
-            args.remote,
-            args.size,
-            args.interval,
-            args.csv,
-            args.spawn,
-            flag,
-        )
-        .await
-        .unwrap();
+        if let Some(trace) = trace {
+            run_trace(
+                args.address,
+                args.remote,
+                args.size,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+                trace,
+                flag,
+            )
+            .await
+            .unwrap();
+        } else if args.multicast {
+            run_multicast(
+                args.address,
+                args.remote,
+                args.interface,
+                args.size,
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+                args.receivers,
+                Duration::from_millis(args.timeout),
+                flag,
+            )
+            .await
+            .unwrap();
+        } else if args.clients > 1 {
+            run_fan_in(
+                args.address,
+                args.remote,
+                args.size,
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+                args.clients,
+                flag,
+            )
+            .await
+            .unwrap();
+        } else if args.depth > 0 {
+            run_depth(
+                args.address,
+                args.remote,
+                args.size,
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.depth,
+                Duration::from_millis(args.timeout),
+                flag,
+            )
+            .await
+            .unwrap();
+        } else {
+            run_wait(
+                args.address,
+                args.remote,
+                args.size,
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+                poll,
+                args.rate
+                    .map(|rate| Schedule::new(args.arrival, rate, args.on, args.off)),
+                flag,
+            )
+            .await
+            .unwrap();
+        }
     });
 }
@@ -0,0 +1,53 @@
+use smol::io::{AsyncRead, AsyncWrite};
+use smol::net::unix::UnixStream;
+use smol::prelude::*;
+use smol::Async;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsFd;
+use std::os::unix::net::UnixStream as StdUnixStream;
+
+async fn run(kind: &str, size: usize) -> Result<(), Box<dyn std::error::Error>> {
+    // The ping hands us the channel as stdin and stdout.
+    let stdin = io::stdin().as_fd().try_clone_to_owned()?;
+    let stdout = io::stdout().as_fd().try_clone_to_owned()?;
+
+    let (mut reader, mut writer): (Box<dyn AsyncRead + Unpin>, Box<dyn AsyncWrite + Unpin>) =
+        match kind {
+            "socketpair" => {
+                let stream = UnixStream::from(Async::new(StdUnixStream::from(stdin))?);
+                (Box::new(stream.clone()), Box::new(stream))
+            }
+            "pipe" => (
+                Box::new(Async::new(File::from(stdin))?),
+                Box::new(Async::new(File::from(stdout))?),
+            ),
+            _ => return Err(format!("Unknown IPC kind: {}", kind).into()),
+        };
+
+    let mut buf = vec![0u8; size];
+    loop {
+        match reader.read_exact(&mut buf).await {
+            Ok(_) => writer.write_all(&buf).await?,
+            // The ping closed its end of the channel
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn main() {
+    let kind: String = env::args()
+        .nth(1)
+        .expect("First argument must be the IPC kind (socketpair or pipe)");
+    let size: usize = env::args()
+        .nth(2)
+        .unwrap()
+        .parse()
+        .expect("Second argument must be the buffer size");
+
+    smol::block_on(async {
+        run(&kind, size).await.unwrap();
+    });
+}
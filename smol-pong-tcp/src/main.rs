@@ -1,20 +1,754 @@
-use smol::net::TcpListener;
+use clap::{CommandFactory, Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use smol::net::{TcpListener, TcpStream};
 use smol::prelude::*;
-use std::env;
+use smol::{Async, Timer};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::io::{Read, Write};
+use std::mem::ManuallyDrop;
 use std::net::SocketAddr;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
-async fn run(addr: SocketAddr, size: usize) -> Result<(), Box<dyn std::error::Error>> {
-    let listener = TcpListener::bind(addr).await?;
+// Sequence number of the messages asking to switch to the size that follows it
+const RESIZE: u64 = u64::MAX;
+// Length prefix of the frames, see read_frame
+const PREFIX: usize = 4;
+
+#[derive(Parser, Debug)]
+struct Args {
+    address: SocketAddr,
+    size: usize,
+    #[clap(long)]
+    ipv6_only: bool,
+    #[clap(long)]
+    fast_open: bool,
+    #[clap(long)]
+    framed: bool,
+    #[clap(long, default_value = "16777216")]
+    max_frame: usize,
+    #[clap(long, value_enum, default_value = "echo")]
+    mode: Mode,
+    #[clap(long)]
+    reply: Option<usize>,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(long, value_enum, default_value = "none")]
+    work: Work,
+    #[clap(long, default_value = "0", value_parser = parse_service)]
+    service: f64,
+    #[clap(long, value_enum, default_value = "fixed")]
+    distribution: Distribution,
+    #[clap(long, default_value = "1")]
+    sigma: f64,
+    #[clap(long)]
+    busy_poll: bool,
+    #[clap(long, default_value = "0")]
+    so_busy_poll: u32,
+    // shared, or local with --per-core
+    #[clap(long, value_enum)]
+    executor: Option<ExecutorKind>,
+    #[clap(long, default_value = "1", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: usize,
+    // Pins its threads itself, on the cores taskset left us
+    #[clap(long, conflicts_with_all = &["cpus", "main-cpu", "reactor-cpu"])]
+    per_core: bool,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// Where the tasks run: an executor shared by `threads` threads, like smol's
+// global one, or a local one run by the main thread next to the test.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExecutorKind {
+    Shared,
+    Local,
+}
+
+// How the tasks are executed. It goes in the framework column, plain smol for
+// the shared executor on one thread that smol::spawn would use.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    kind: ExecutorKind,
+    threads: usize,
+    per_core: Option<usize>,
+    pinning: Pinning,
+}
+
+static EXECUTOR: smol::Executor<'static> = smol::Executor::new();
+static KIND: OnceLock<ExecutorKind> = OnceLock::new();
+
+thread_local! {
+    static LOCAL: smol::LocalExecutor<'static> = const { smol::LocalExecutor::new() };
+}
+
+// smol::spawn on the executor picked by --executor
+fn spawn<T: Send + 'static>(
+    future: impl std::future::Future<Output = T> + Send + 'static,
+) -> smol::Task<T> {
+    match KIND.get() {
+        Some(ExecutorKind::Local) => LOCAL.with(|local| local.spawn(future)),
+        _ => EXECUTOR.spawn(future),
+    }
+}
+
+impl RuntimeConfig {
+    // Starts the executor threads and blocks the main thread on `future`
+    fn block_on<T>(&self, future: impl std::future::Future<Output = T>) -> T {
+        // Again on each core with --per-core
+        KIND.get_or_init(|| self.kind);
+        // async-io starts it on the first socket or timer, the test starts
+        // once it is in place
+        if let Some(core) = self.pinning.reactor {
+            let watcher = pin_named("async-io", 1, move |_| core);
+            smol::block_on(smol::Timer::after(Duration::from_millis(1)));
+            watcher.join().unwrap();
+        }
+        match self.kind {
+            ExecutorKind::Shared => {
+                for n in 1..=self.threads {
+                    let core = self.pinning.worker(n - 1);
+                    std::thread::Builder::new()
+                        .name(format!("smol-{}", n))
+                        .spawn(move || {
+                            if let Some(core) = core {
+                                pin(core).unwrap();
+                            }
+                            smol::block_on(EXECUTOR.run(smol::future::pending::<()>()))
+                        })
+                        .unwrap();
+                }
+                // Last, for the executor threads not to inherit it
+                self.pinning.pin_main();
+                smol::block_on(future)
+            }
+            ExecutorKind::Local => {
+                self.pinning.pin_main();
+                LOCAL.with(|local| smol::block_on(local.run(future)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "smol")?;
+        if let ExecutorKind::Local = self.kind {
+            write!(f, "/local")?;
+        }
+        if self.threads != 1 {
+            write!(f, "/threads={}", self.threads)?;
+        }
+        if let Some(n) = self.per_core {
+            write!(f, "/per-core={}", n)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// What the pong does with the requests: send them back, answer them with
+// `reply` bytes, swallow them, or answer the first one with an endless stream
+// of `reply` bytes messages. The udp and ipc pongs only echo.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Mode {
+    Echo,
+    Fixed,
+    Sink,
+    Source,
+}
+
+// What the pong does before answering a request: nothing, keep the CPU busy,
+// block the thread it runs on, block a thread of the blocking pool, or await
+// a timer.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Work {
+    None,
+    Spin,
+    Block,
+    Offload,
+    Timer,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Distribution {
+    Fixed,
+    Exponential,
+    Lognormal,
+}
+
+// Service times are `mean` microseconds on average, `sigma` is the spread of
+// the lognormal ones.
+#[derive(Clone, Copy, Debug)]
+struct Service {
+    work: Work,
+    distribution: Distribution,
+    mean: f64,
+    sigma: f64,
+}
+
+impl Service {
+    fn duration(&self, rng: &mut StdRng) -> Duration {
+        let micros = match self.distribution {
+            Distribution::Fixed => self.mean,
+            Distribution::Exponential => -(1.0 - rng.gen::<f64>()).ln() * self.mean,
+            // Box-Muller, with the location shifted for the mean to stay `mean`
+            Distribution::Lognormal => {
+                let z = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * rng.gen::<f64>()).cos();
+                (self.mean.ln() - self.sigma * self.sigma / 2.0 + self.sigma * z).exp()
+            }
+        };
+        Duration::from_secs_f64(micros / 1e6)
+    }
+}
+
+// Service times are waited out, they cannot be negative
+fn parse_service(mean: &str) -> Result<f64, String> {
+    match mean.parse::<f64>() {
+        Ok(micros) if micros >= 0.0 && micros.is_finite() => Ok(micros),
+        Ok(_) => Err(format!("{}: the service time has to be 0 or more", mean)),
+        Err(e) => Err(format!("{:?}: {}", mean, e)),
+    }
+}
+
+// How the sockets wait for data: parked on the runtime's reactor or busy
+// polled, and the SO_BUSY_POLL budget in microseconds, 0 is off.
+#[derive(Clone, Copy, Debug)]
+struct Poll {
+    busy: bool,
+    budget: u32,
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+// SO_BUSY_POLL: blocking reads and epoll waits on the socket poll the device
+// queue for up to `micros` before going to sleep.
+fn set_busy_poll(socket: &impl AsRawFd, micros: u32) -> std::io::Result<()> {
+    let value = micros as libc::c_int;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BUSY_POLL,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+// CPU time of the whole process, every thread included
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+// The std socket behind an async one, for non-blocking calls on it
+fn borrow_std<T: FromRawFd>(socket: &impl AsRawFd) -> ManuallyDrop<T> {
+    ManuallyDrop::new(unsafe { T::from_raw_fd(socket.as_raw_fd()) })
+}
+
+// Busy polling retries a non-blocking call until it goes through, yielding in
+// between so that the runtime never parks in epoll waiting for the socket.
+async fn spin<R>(mut op: impl FnMut() -> std::io::Result<R>) -> std::io::Result<R> {
+    loop {
+        match op() {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => smol::future::yield_now().await,
+            res => return res,
+        }
+    }
+}
+
+async fn spin_read_exact(stream: &std::net::TcpStream, buf: &mut [u8]) -> std::io::Result<()> {
+    let mut read = 0;
+    while read < buf.len() {
+        let n = spin(|| (&*stream).read(&mut buf[read..])).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        read += n;
+    }
+    Ok(())
+}
+
+async fn spin_write_all(stream: &std::net::TcpStream, buf: &[u8]) -> std::io::Result<()> {
+    let mut written = 0;
+    while written < buf.len() {
+        written += spin(|| (&*stream).write(&buf[written..])).await?;
+    }
+    Ok(())
+}
+
+async fn process(service: &Service, rng: &mut StdRng) {
+    if let Work::None = service.work {
+        return;
+    }
+    let duration = service.duration(rng);
+    match service.work {
+        Work::None => {}
+        Work::Spin => {
+            let start = Instant::now();
+            while start.elapsed() < duration {
+                std::hint::spin_loop();
+            }
+        }
+        // Stalls whatever else runs on this thread
+        Work::Block => thread::sleep(duration),
+        Work::Offload => smol::unblock(move || thread::sleep(duration)).await,
+        Work::Timer => {
+            Timer::after(duration).await;
+        }
+    }
+}
+
+// SO_REUSEPORT: sockets bound to the same port share it, the kernel spreads
+// the connections or datagrams among them by a hash of the source.
+fn set_reuse_port(socket: &impl AsRawFd) -> std::io::Result<()> {
+    let value: libc::c_int = 1;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// The cores the process may run on, as taskset left them
+fn cores() -> std::io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize)
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+        .collect())
+}
+
+// Thread per core: `serve` runs again on a thread pinned to each of `cores`,
+// nothing is shared between them but the port.
+fn per_core(cores: &[usize], serve: impl Fn() + Sync) {
+    thread::scope(|scope| {
+        for &core in cores {
+            let serve = &serve;
+            thread::Builder::new()
+                .name(format!("pong-{}", core))
+                .spawn_scoped(scope, move || {
+                    pin(core).unwrap();
+                    serve();
+                })
+                .unwrap();
+        }
+    });
+}
+
+// IPv6 listeners are dual-stack unless `ipv6_only` is set.
+fn listen(
+    addr: SocketAddr,
+    ipv6_only: bool,
+    fast_open: bool,
+    reuse_port: bool,
+) -> std::io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
+    socket.set_reuse_address(true)?;
+    if reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    if fast_open {
+        // Length of the queue of Fast Open requests still in the handshake
+        let qlen: libc::c_int = 1024;
+        let res = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_FASTOPEN,
+                &qlen as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if res != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+// Thousands of connections need as many file descriptors
+fn raise_fd_limit() -> std::io::Result<()> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    limit.rlim_cur = limit.rlim_max;
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// The new message size if this is a resize request. Sizes over max bytes are
+// refused like frames over --max-frame.
+fn resize(buf: &[u8], max: usize) -> std::io::Result<Option<usize>> {
+    if buf.len() < 16 {
+        return Ok(None);
+    }
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[0..8]);
+    if u64::from_le_bytes(bytes) != RESIZE {
+        return Ok(None);
+    }
+    bytes.copy_from_slice(&buf[8..16]);
+    let size = u64::from_le_bytes(bytes);
+    if size > max as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("resize to {} bytes over --max-frame {}", size, max),
+        ));
+    }
+    Ok(Some(size as usize))
+}
+
+// Frames are a little-endian u32 length followed by that many bytes. Reads one
+// into buf, growing it as needed, and returns its length prefix included.
+// Frames over max bytes are refused before anything is allocated for them.
+async fn read_frame(
+    stream: &mut TcpStream,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> std::io::Result<usize> {
+    let mut prefix = [0u8; PREFIX];
+    stream.read_exact(&mut prefix).await?;
+    let frame = u32::from_le_bytes(prefix) as usize;
+    if frame > max {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("frame of {} bytes over --max-frame {}", frame, max),
+        ));
+    }
+    let len = PREFIX + frame;
+    if buf.len() < len {
+        buf.resize(len, 0);
+    }
+    buf[..PREFIX].copy_from_slice(&prefix);
+    stream.read_exact(&mut buf[PREFIX..len]).await?;
+    Ok(len)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn serve(
+    mut stream: TcpStream,
+    size: usize,
+    mode: Mode,
+    reply: usize,
+    framed: bool,
+    max_frame: usize,
+    service: Service,
+    served: &mut u64,
+) -> std::io::Result<()> {
+    let mut buf = vec![0u8; size];
+    let mut rng = StdRng::from_entropy();
+    // Fixed and source replies are framed like the requests
+    let header = if framed { PREFIX } else { 0 };
+    let mut response = vec![0u8; header + reply];
+    if framed {
+        response[..PREFIX].copy_from_slice(&(reply as u32).to_le_bytes());
+    }
+
+    loop {
+        let len = if framed {
+            read_frame(&mut stream, &mut buf, max_frame).await?
+        } else {
+            stream.read_exact(&mut buf).await?;
+            buf.len()
+        };
+        *served += 1;
+        process(&service, &mut rng).await;
+        match mode {
+            Mode::Echo => {
+                stream.write_all(&buf[..len]).await?;
+                // The ping switches size once the echo of its request is back
+                if !framed {
+                    if let Some(size) = resize(&buf, max_frame)? {
+                        buf.resize(size, 0);
+                    }
+                }
+            }
+            Mode::Fixed => {
+                // The reply carries the sequence number of its request
+                let seq = (len - header).min(reply).min(8);
+                response[header..header + seq].copy_from_slice(&buf[header..header + seq]);
+                stream.write_all(&response).await?;
+            }
+            Mode::Sink => {}
+            Mode::Source => {
+                let mut count: u64 = 0;
+                loop {
+                    let seq = reply.min(8);
+                    response[header..header + seq].copy_from_slice(&count.to_le_bytes()[..seq]);
+                    stream.write_all(&response).await?;
+                    count = count.wrapping_add(1);
+                }
+            }
+        }
+    }
+}
+
+// Echoes like serve, busy polling the socket instead of waiting on the runtime
+async fn serve_busy(
+    stream: TcpStream,
+    size: usize,
+    max_frame: usize,
+    service: Service,
+    served: &mut u64,
+) -> std::io::Result<()> {
+    let socket = borrow_std::<std::net::TcpStream>(&stream);
+    let mut buf = vec![0u8; size];
+    let mut rng = StdRng::from_entropy();
+
+    loop {
+        spin_read_exact(&socket, &mut buf).await?;
+        *served += 1;
+        process(&service, &mut rng).await;
+        spin_write_all(&socket, &buf).await?;
+        // The ping switches size once the echo of its request is back
+        if let Some(size) = resize(&buf, max_frame)? {
+            buf.resize(size, 0);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run(
+    addr: SocketAddr,
+    size: usize,
+    ipv6_only: bool,
+    fast_open: bool,
+    reuse_port: bool,
+    mode: Mode,
+    reply: usize,
+    framed: bool,
+    max_frame: usize,
+    service: Service,
+    poll: Poll,
+    csv: bool,
+    runtime: RuntimeConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::from(Async::new(listen(addr, ipv6_only, fast_open, reuse_port)?)?);
     let mut incoming = listener.incoming();
 
     while let Some(stream) = incoming.next().await {
-        let mut stream = stream.unwrap();
+        let stream = stream.unwrap();
         stream.set_nodelay(true)?;
-        smol::spawn(async move {
-            let mut buf = vec![0u8; size];
-            loop {
-                stream.read_exact(&mut buf).await.unwrap();
-                stream.write_all(&buf).await.unwrap();
+        if poll.budget > 0 {
+            set_busy_poll(&stream, poll.budget)?;
+        }
+        spawn(async move {
+            let peer = stream.peer_addr();
+            let cpu = cpu_time();
+            let start = Instant::now();
+            let mut served = 0;
+            // A client going away must not take the other ones down
+            let _ = if poll.busy {
+                serve_busy(stream, size, max_frame, service, &mut served).await
+            } else {
+                serve(
+                    stream,
+                    size,
+                    mode,
+                    reply,
+                    framed,
+                    max_frame,
+                    service,
+                    &mut served,
+                )
+                .await
+            };
+            // CPU use of the whole pong while the client was connected
+            let usage = (cpu_time() - cpu).as_secs_f64() / start.elapsed().as_secs_f64();
+            if let Ok(peer) = peer {
+                if csv {
+                    // No load tasks run in the pong, its tasks read like those of an idle ping
+                    // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer
+                    println!(
+                        "{},tcp,cpu,{},0,{},sleep:0,{:.1},%,{},{}",
+                        runtime,
+                        served,
+                        size,
+                        usage * 100.0,
+                        family(peer),
+                        peer
+                    );
+                } else {
+                    println!(
+                        "{}: requests={} cpu={:.1}% over {:?}",
+                        peer,
+                        served,
+                        usage * 100.0,
+                        start.elapsed()
+                    );
+                }
             }
         })
         .detach();
@@ -23,19 +757,98 @@ async fn run(addr: SocketAddr, size: usize) -> Result<(), Box<dyn std::error::Er
     Ok(())
 }
 
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    // Busy polling only echoes
+    if args.busy_poll && (!matches!(args.mode, Mode::Echo) || args.framed) {
+        return Err(conflict(
+            "Busy polling needs the echo mode without --framed",
+        ));
+    }
+    // --per-core runs a local executor on each core
+    let shared = args
+        .executor
+        .map_or(!args.per_core, |kind| matches!(kind, ExecutorKind::Shared));
+    if args.per_core && shared {
+        return Err(conflict(
+            "--per-core runs a local executor on each core, not the shared one",
+        ));
+    }
+    // The local executor runs on the main thread
+    if !shared && args.threads != 1 {
+        return Err(conflict(
+            "--threads needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    if !shared && args.cpus.is_some() {
+        return Err(conflict(
+            "--cpus needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    Ok(())
+}
+
 fn main() {
-    let addr: SocketAddr = env::args()
-        .nth(1)
-        .unwrap()
-        .parse()
-        .expect("First argument must be a valid socket address");
-    let size: usize = env::args()
-        .nth(2)
-        .unwrap()
-        .parse()
-        .expect("Second argument must be the buffer size");
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+    raise_fd_limit().unwrap();
+    let service = Service {
+        work: args.work,
+        distribution: args.distribution,
+        mean: args.service,
+        sigma: args.sigma,
+    };
+    let poll = Poll {
+        busy: args.busy_poll,
+        budget: args.so_busy_poll,
+    };
 
-    smol::block_on(async {
-        run(addr, size).await.unwrap();
-    });
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    // Thread per core: a runtime and a socket on each core taskset left us
+    let cores = args.per_core.then(|| cores().unwrap());
+    let reuse_port = cores.is_some();
+    let runtime = RuntimeConfig {
+        // --per-core runs a local executor on each core
+        kind: args.executor.unwrap_or(if cores.is_some() {
+            ExecutorKind::Local
+        } else {
+            ExecutorKind::Shared
+        }),
+        threads: args.threads,
+        per_core: cores.as_ref().map(Vec::len),
+        pinning,
+    };
+
+    // All of it on this thread, or again on each core with --per-core
+    let serve = || {
+        runtime.block_on(async {
+            run(
+                args.address,
+                args.size,
+                args.ipv6_only,
+                args.fast_open,
+                reuse_port,
+                args.mode,
+                args.reply.unwrap_or(args.size),
+                args.framed,
+                args.max_frame,
+                service,
+                poll,
+                args.csv,
+                runtime,
+            )
+            .await
+            .unwrap();
+        });
+    };
+    match cores {
+        Some(cores) => per_core(&cores, serve),
+        None => serve(),
+    }
 }
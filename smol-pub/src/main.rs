@@ -0,0 +1,395 @@
+use clap::{CommandFactory, Parser, ValueEnum};
+use smol::net::{TcpListener, UdpSocket};
+use smol::prelude::*;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Sequence number announcing the end of the test to the subscribers
+const END: u64 = u64::MAX;
+// seq, send time, interval
+const HEADER: usize = 24;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Tcp,
+    Udp,
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    address: SocketAddr,
+    // Room for the header at least
+    #[clap(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(HEADER as u64..))]
+    size: usize,
+    interval: f64,
+    #[clap(short = 'n', long, default_value = "1")]
+    subscribers: usize,
+    #[clap(short, long, default_value = "60")]
+    duration: u64,
+    #[clap(long, value_enum, default_value = "shared")]
+    executor: ExecutorKind,
+    #[clap(long, default_value = "1", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: usize,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// Where the tasks run: an executor shared by `threads` threads, like smol's
+// global one, or a local one run by the main thread next to the test.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExecutorKind {
+    Shared,
+    Local,
+}
+
+// How the tasks are executed. It goes in the framework column, plain smol for
+// the shared executor on one thread that smol::spawn would use.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    kind: ExecutorKind,
+    threads: usize,
+    pinning: Pinning,
+}
+
+static EXECUTOR: smol::Executor<'static> = smol::Executor::new();
+static KIND: OnceLock<ExecutorKind> = OnceLock::new();
+
+thread_local! {
+    static LOCAL: smol::LocalExecutor<'static> = const { smol::LocalExecutor::new() };
+}
+
+impl RuntimeConfig {
+    // Starts the executor threads and blocks the main thread on `future`
+    fn block_on<T>(&self, future: impl std::future::Future<Output = T>) -> T {
+        KIND.set(self.kind).unwrap();
+        // async-io starts it on the first socket or timer, the test starts
+        // once it is in place
+        if let Some(core) = self.pinning.reactor {
+            let watcher = pin_named("async-io", 1, move |_| core);
+            smol::block_on(smol::Timer::after(Duration::from_millis(1)));
+            watcher.join().unwrap();
+        }
+        match self.kind {
+            ExecutorKind::Shared => {
+                for n in 1..=self.threads {
+                    let core = self.pinning.worker(n - 1);
+                    std::thread::Builder::new()
+                        .name(format!("smol-{}", n))
+                        .spawn(move || {
+                            if let Some(core) = core {
+                                pin(core).unwrap();
+                            }
+                            smol::block_on(EXECUTOR.run(smol::future::pending::<()>()))
+                        })
+                        .unwrap();
+                }
+                // Last, for the executor threads not to inherit it
+                self.pinning.pin_main();
+                smol::block_on(future)
+            }
+            ExecutorKind::Local => {
+                self.pinning.pin_main();
+                LOCAL.with(|local| smol::block_on(local.run(future)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "smol")?;
+        if let ExecutorKind::Local = self.kind {
+            write!(f, "/local")?;
+        }
+        if self.threads != 1 {
+            write!(f, "/threads={}", self.threads)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+// Instants cannot be compared across processes, CLOCK_MONOTONIC can.
+fn monotonic() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+fn stamp(payload: &mut [u8], count: u64, interval: f64) {
+    payload[0..8].copy_from_slice(&count.to_le_bytes());
+    payload[8..16].copy_from_slice(&monotonic().to_le_bytes());
+    payload[16..24].copy_from_slice(&interval.to_le_bytes());
+}
+
+async fn run_tcp(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    subscribers: usize,
+    duration: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(address).await?;
+    let mut streams = Vec::with_capacity(subscribers);
+    while streams.len() < subscribers {
+        let (stream, _src) = listener.accept().await?;
+        stream.set_nodelay(true)?;
+        streams.push(stream);
+    }
+
+    // The test starts once all the subscribers are there
+    let start = Instant::now();
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+    while start.elapsed() < duration {
+        stamp(&mut payload, count, interval);
+        for stream in streams.iter_mut() {
+            stream.write_all(&payload).await?;
+        }
+
+        smol::unblock(move || std::thread::sleep(Duration::from_secs_f64(interval))).await;
+        count = count.wrapping_add(1);
+    }
+
+    stamp(&mut payload, END, interval);
+    for stream in streams.iter_mut() {
+        stream.write_all(&payload).await?;
+    }
+    Ok(())
+}
+
+async fn run_udp(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    subscribers: usize,
+    duration: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::bind(address).await?;
+    let mut payload = vec![0u8; size];
+
+    // Subscribers announce themselves with an empty datagram
+    let mut peers = Vec::with_capacity(subscribers);
+    while peers.len() < subscribers {
+        let (_, peer) = socket.recv_from(&mut payload).await?;
+        if !peers.contains(&peer) {
+            peers.push(peer);
+        }
+    }
+
+    let start = Instant::now();
+    let mut count: u64 = 0;
+    while start.elapsed() < duration {
+        stamp(&mut payload, count, interval);
+        for peer in peers.iter() {
+            socket.send_to(&payload, peer).await?;
+        }
+
+        smol::unblock(move || std::thread::sleep(Duration::from_secs_f64(interval))).await;
+        count = count.wrapping_add(1);
+    }
+
+    stamp(&mut payload, END, interval);
+    for peer in peers.iter() {
+        socket.send_to(&payload, peer).await?;
+    }
+    Ok(())
+}
+
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    let shared = matches!(args.executor, ExecutorKind::Shared);
+    // The local executor runs on the main thread
+    if !shared && args.threads != 1 {
+        return Err(conflict(
+            "--threads needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    if !shared && args.cpus.is_some() {
+        return Err(conflict(
+            "--cpus needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        kind: args.executor,
+        threads: args.threads,
+        pinning,
+    };
+
+    runtime.block_on(async {
+        let duration = Duration::from_secs(args.duration);
+        match args.kind {
+            Kind::Tcp => run_tcp(
+                args.address,
+                args.size,
+                args.interval,
+                args.subscribers,
+                duration,
+            )
+            .await
+            .unwrap(),
+            Kind::Udp => run_udp(
+                args.address,
+                args.size,
+                args.interval,
+                args.subscribers,
+                duration,
+            )
+            .await
+            .unwrap(),
+        }
+    });
+}
@@ -0,0 +1,534 @@
+use clap::{CommandFactory, Parser, ValueEnum};
+use smol::net::{TcpStream, UdpSocket};
+use smol::prelude::*;
+use smol::Timer;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Sequence number announcing the end of the test to the subscribers
+const END: u64 = u64::MAX;
+// seq, send time, interval
+const HEADER: usize = 24;
+const PERCENTILES: [(&str, f64); 5] = [
+    ("p50", 0.5),
+    ("p90", 0.9),
+    ("p99", 0.99),
+    ("p999", 0.999),
+    ("max", 1.0),
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Tcp,
+    Udp,
+}
+
+impl Kind {
+    fn transport(&self) -> &'static str {
+        match self {
+            Kind::Tcp => "tcp",
+            Kind::Udp => "udp",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    address: SocketAddr,
+    // Room for the header at least
+    #[clap(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(HEADER as u64..))]
+    size: usize,
+    #[clap(short = 'n', long, default_value = "1")]
+    subscribers: usize,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(short, long, default_value = "5")]
+    timeout: u64,
+    #[clap(long, value_enum, default_value = "shared")]
+    executor: ExecutorKind,
+    #[clap(long, default_value = "1", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    threads: usize,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// Where the tasks run: an executor shared by `threads` threads, like smol's
+// global one, or a local one run by the main thread next to the test.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExecutorKind {
+    Shared,
+    Local,
+}
+
+// How the tasks are executed. It goes in the framework column, plain smol for
+// the shared executor on one thread that smol::spawn would use.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    kind: ExecutorKind,
+    threads: usize,
+    pinning: Pinning,
+}
+
+static EXECUTOR: smol::Executor<'static> = smol::Executor::new();
+static KIND: OnceLock<ExecutorKind> = OnceLock::new();
+
+thread_local! {
+    static LOCAL: smol::LocalExecutor<'static> = const { smol::LocalExecutor::new() };
+}
+
+// smol::spawn on the executor picked by --executor
+fn spawn<T: Send + 'static>(
+    future: impl std::future::Future<Output = T> + Send + 'static,
+) -> smol::Task<T> {
+    match KIND.get() {
+        Some(ExecutorKind::Local) => LOCAL.with(|local| local.spawn(future)),
+        _ => EXECUTOR.spawn(future),
+    }
+}
+
+impl RuntimeConfig {
+    // Starts the executor threads and blocks the main thread on `future`
+    fn block_on<T>(&self, future: impl std::future::Future<Output = T>) -> T {
+        KIND.set(self.kind).unwrap();
+        // async-io starts it on the first socket or timer, the test starts
+        // once it is in place
+        if let Some(core) = self.pinning.reactor {
+            let watcher = pin_named("async-io", 1, move |_| core);
+            smol::block_on(smol::Timer::after(Duration::from_millis(1)));
+            watcher.join().unwrap();
+        }
+        match self.kind {
+            ExecutorKind::Shared => {
+                for n in 1..=self.threads {
+                    let core = self.pinning.worker(n - 1);
+                    std::thread::Builder::new()
+                        .name(format!("smol-{}", n))
+                        .spawn(move || {
+                            if let Some(core) = core {
+                                pin(core).unwrap();
+                            }
+                            smol::block_on(EXECUTOR.run(smol::future::pending::<()>()))
+                        })
+                        .unwrap();
+                }
+                // Last, for the executor threads not to inherit it
+                self.pinning.pin_main();
+                smol::block_on(future)
+            }
+            ExecutorKind::Local => {
+                self.pinning.pin_main();
+                LOCAL.with(|local| smol::block_on(local.run(future)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "smol")?;
+        if let ExecutorKind::Local = self.kind {
+            write!(f, "/local")?;
+        }
+        if self.threads != 1 {
+            write!(f, "/threads={}", self.threads)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// One-way latencies (seq, ns) seen by a subscriber, and the publication interval
+struct Samples {
+    interval: f64,
+    latencies: Vec<(u64, u64)>,
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// Pins the threads the runtime starts on its own, found by their name as they
+// show up: `count` of them, the nth on `core(n)`. Gives up after ten seconds.
+fn pin_named(
+    name: &'static str,
+    count: usize,
+    core: impl Fn(usize) -> usize + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("pinning".to_string())
+        .spawn(move || {
+            // The kernel keeps the first 15 bytes of a thread name
+            let comm = &name[..name.len().min(15)];
+            let mut pinned = Vec::new();
+            let start = Instant::now();
+            while pinned.len() < count && start.elapsed() < Duration::from_secs(10) {
+                for entry in std::fs::read_dir("/proc/self/task").unwrap().flatten() {
+                    let tid: libc::pid_t = entry.file_name().to_string_lossy().parse().unwrap();
+                    let found = std::fs::read_to_string(entry.path().join("comm"))
+                        .is_ok_and(|c| c.trim_end() == comm);
+                    if found && pinned.len() < count && !pinned.contains(&tid) {
+                        pin_thread(tid, name, core(pinned.len())).unwrap();
+                        pinned.push(tid);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            if pinned.len() < count {
+                eprintln!("{}: {} of {} threads pinned", name, pinned.len(), count);
+            }
+        })
+        .unwrap()
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+// Instants cannot be compared across processes, CLOCK_MONOTONIC can.
+fn monotonic() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+// Returns false once the publisher is done.
+fn record(payload: &[u8], samples: &mut Samples) -> bool {
+    let now = monotonic();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&payload[0..8]);
+    let count = u64::from_le_bytes(bytes);
+    if count == END {
+        return false;
+    }
+    bytes.copy_from_slice(&payload[8..16]);
+    let sent = u64::from_le_bytes(bytes);
+    bytes.copy_from_slice(&payload[16..24]);
+    samples.interval = f64::from_le_bytes(bytes);
+    samples.latencies.push((count, now.saturating_sub(sent)));
+    true
+}
+
+async fn subscribe_tcp(address: SocketAddr, size: usize) -> io::Result<Samples> {
+    let mut stream = TcpStream::connect(address).await?;
+    stream.set_nodelay(true)?;
+    let mut payload = vec![0u8; size];
+    let mut samples = Samples {
+        interval: 0.0,
+        latencies: Vec::new(),
+    };
+
+    loop {
+        match stream.read_exact(&mut payload).await {
+            Ok(_) => {
+                if !record(&payload, &mut samples) {
+                    return Ok(samples);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(samples),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn subscribe_udp(address: SocketAddr, size: usize, timeout: Duration) -> io::Result<Samples> {
+    let unspecified: IpAddr = match address {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
+    socket.connect(address).await?;
+    socket.send(&[]).await?;
+    let mut payload = vec![0u8; size];
+    let mut samples = Samples {
+        interval: 0.0,
+        latencies: Vec::new(),
+    };
+
+    // A lost end of test must not hang the subscriber
+    loop {
+        let recv = async { Some(socket.recv(&mut payload).await) };
+        let expire = async {
+            Timer::after(timeout).await;
+            None
+        };
+        match recv.or(expire).await {
+            Some(res) => {
+                res?;
+                if !record(&payload, &mut samples) {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+    Ok(samples)
+}
+
+fn percentile(sorted: &[u64], q: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let i = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[i - 1]
+}
+
+fn report(
+    kind: Kind,
+    family: &str,
+    size: usize,
+    subscribers: Vec<Samples>,
+    csv: bool,
+    runtime: RuntimeConfig,
+) {
+    let n = subscribers.len();
+    let interval = subscribers.iter().map(|s| s.interval).fold(0.0, f64::max);
+    let mut percentiles = Vec::with_capacity(n);
+
+    for (id, samples) in subscribers.iter().enumerate() {
+        for (count, latency) in samples.latencies.iter() {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+                println!(
+                    "{},{},oneway,{},{},{},0,{},ns,{},{},{}",
+                    runtime,
+                    kind.transport(),
+                    count,
+                    interval,
+                    size,
+                    latency,
+                    family,
+                    id,
+                    n
+                );
+            } else {
+                println!(
+                    "{} bytes: sub={} seq={} time={:?}",
+                    size,
+                    id,
+                    count,
+                    Duration::from_nanos(*latency)
+                );
+            }
+        }
+
+        let mut sorted: Vec<u64> = samples.latencies.iter().map(|(_, l)| *l).collect();
+        sorted.sort_unstable();
+        let p: Vec<u64> = PERCENTILES
+            .iter()
+            .map(|(_, q)| percentile(&sorted, *q))
+            .collect();
+        for ((name, _), value) in PERCENTILES.iter().zip(p.iter()) {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+                println!(
+                    "{},{},oneway-{},{},{},{},0,{},ns,{},{},{}",
+                    runtime,
+                    kind.transport(),
+                    name,
+                    sorted.len(),
+                    interval,
+                    size,
+                    value,
+                    family,
+                    id,
+                    n
+                );
+            } else {
+                println!("sub={} {}={:?}", id, name, Duration::from_nanos(*value));
+            }
+        }
+        percentiles.push(p);
+    }
+
+    // How far the slowest subscriber lags behind the fastest one
+    for (i, (name, _)) in PERCENTILES.iter().enumerate() {
+        let fastest = percentiles.iter().map(|p| p[i]).min().unwrap_or(0);
+        let slowest = percentiles.iter().map(|p| p[i]).max().unwrap_or(0);
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+            println!(
+                "{},{},spread-{},{},{},{},0,{},ns,{},all,{}",
+                runtime,
+                kind.transport(),
+                name,
+                n,
+                interval,
+                size,
+                slowest - fastest,
+                family,
+                n
+            );
+        } else {
+            println!(
+                "spread {}={:?}",
+                name,
+                Duration::from_nanos(slowest - fastest)
+            );
+        }
+    }
+}
+
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    let shared = matches!(args.executor, ExecutorKind::Shared);
+    // The local executor runs on the main thread
+    if !shared && args.threads != 1 {
+        return Err(conflict(
+            "--threads needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    if !shared && args.cpus.is_some() {
+        return Err(conflict(
+            "--cpus needs the shared executor, the local one runs on the main thread",
+        ));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        kind: args.executor,
+        threads: args.threads,
+        pinning,
+    };
+
+    runtime.block_on(async {
+        let timeout = Duration::from_secs(args.timeout);
+        let handles: Vec<_> = (0..args.subscribers)
+            .map(|_| match args.kind {
+                Kind::Tcp => spawn(subscribe_tcp(args.address, args.size)),
+                Kind::Udp => spawn(subscribe_udp(args.address, args.size, timeout)),
+            })
+            .collect();
+
+        let mut subscribers = Vec::with_capacity(handles.len());
+        for handle in handles {
+            subscribers.push(handle.await.unwrap());
+        }
+
+        report(
+            args.kind,
+            family(args.address),
+            args.size,
+            subscribers,
+            args.csv,
+            runtime,
+        );
+    });
+}
@@ -0,0 +1,726 @@
+use clap::{CommandFactory, Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::*;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MAX_SAMPLES: usize = 100_000_000;
+const PONG: &str = "std-pong-ipc";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Socketpair,
+    Pipe,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Socketpair => "socketpair",
+            Kind::Pipe => "pipe",
+        }
+    }
+
+    fn transport(&self) -> &'static str {
+        match self {
+            Kind::Socketpair => "unix",
+            Kind::Pipe => "pipe",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    #[clap(short, long)]
+    wait: bool,
+    #[clap(short, long, default_value = "0")]
+    spawn: usize,
+    #[clap(long, value_enum, default_value = "sleep")]
+    load: Load,
+    #[clap(long, value_enum, default_value = "timer")]
+    pacing: Pacing,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(short, long, default_value = "60")]
+    duration: u64,
+    #[clap(long, default_value = "0")]
+    depth: usize,
+    #[clap(long, value_parser = parse_positive)]
+    rate: Option<f64>,
+    #[clap(long, value_enum, default_value = "constant")]
+    arrival: Arrival,
+    #[clap(long, default_value = "0.1", value_parser = parse_positive)]
+    on: f64,
+    #[clap(long, default_value = "0.9", value_parser = parse_positive)]
+    off: f64,
+}
+
+// Background tasks competing with the test for the runtime: sleeping, spinning
+// without ever yielding, yielding in a tight loop, passing tokens around a ring
+// of channels, blocking the worker thread, or doing socket I/O of their own.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Load {
+    Sleep,
+    Spin,
+    Yield,
+    Channel,
+    Block,
+    Io,
+}
+
+impl Load {
+    fn name(&self) -> &'static str {
+        match self {
+            Load::Sleep => "sleep",
+            Load::Spin => "spin",
+            Load::Yield => "yield",
+            Load::Channel => "channel",
+            Load::Block => "block",
+            Load::Io => "io",
+        }
+    }
+}
+
+// The tasks column, the load profile and how many tasks run it
+#[derive(Clone, Copy, Debug)]
+struct Tasks {
+    load: Load,
+    count: usize,
+}
+
+impl fmt::Display for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.load.name(), self.count)
+    }
+}
+
+// How the pings wait for their next send: the runtime's own timer, a blocking
+// sleep handed to another thread, spinning on the clock, or a timerfd armed with
+// the absolute deadline and read like any other file descriptor.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Pacing {
+    Timer,
+    Offload,
+    Spin,
+    Timerfd,
+}
+
+impl Pacing {
+    fn name(&self) -> &'static str {
+        match self {
+            Pacing::Timer => "timer",
+            Pacing::Offload => "offload",
+            Pacing::Spin => "spin",
+            Pacing::Timerfd => "timerfd",
+        }
+    }
+}
+
+fn timerfd(flags: libc::c_int) -> std::io::Result<OwnedFd> {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags | libc::TFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Arms the timerfd for an absolute deadline, Instant is CLOCK_MONOTONIC on Linux.
+// False if the deadline has passed already, a zero expiry would disarm it.
+fn arm(fd: RawFd, deadline: Instant) -> std::io::Result<bool> {
+    let wait = deadline.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        return Ok(false);
+    }
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let nanos = now.tv_nsec as u64 + wait.subsec_nanos() as u64;
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: now.tv_sec + (wait.as_secs() + nanos / 1_000_000_000) as libc::time_t,
+            tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+        },
+    };
+    let res =
+        unsafe { libc::timerfd_settime(fd, libc::TFD_TIMER_ABSTIME, &spec, std::ptr::null_mut()) };
+    if res < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+// How many times the timerfd expired since it was armed
+fn expirations(fd: RawFd) -> std::io::Result<u64> {
+    let mut count = [0u8; 8];
+    let n = unsafe { libc::read(fd, count.as_mut_ptr() as *mut libc::c_void, count.len()) };
+    if n < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(u64::from_ne_bytes(count))
+}
+
+// Waits with the chosen pacing, the timerfd is created once and armed again
+// for every deadline.
+struct Pacer {
+    pacing: Pacing,
+    timerfd: Option<OwnedFd>,
+}
+
+impl Pacer {
+    fn new(pacing: Pacing) -> std::io::Result<Pacer> {
+        let timerfd = match pacing {
+            Pacing::Timerfd => Some(timerfd(0)?),
+            _ => None,
+        };
+        Ok(Pacer { pacing, timerfd })
+    }
+
+    fn sleep(&self, wait: Duration) {
+        self.until(Instant::now() + wait);
+    }
+
+    fn until(&self, deadline: Instant) {
+        match self.pacing {
+            Pacing::Timer => thread::sleep(deadline.saturating_duration_since(Instant::now())),
+            Pacing::Offload => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                thread::spawn(move || thread::sleep(wait)).join().unwrap();
+            }
+            Pacing::Spin => {
+                while Instant::now() < deadline {
+                    std::hint::spin_loop();
+                }
+            }
+            Pacing::Timerfd => {
+                let timerfd = self.timerfd.as_ref().unwrap();
+                if arm(timerfd.as_raw_fd(), deadline).unwrap() {
+                    expirations(timerfd.as_raw_fd()).unwrap();
+                }
+            }
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Arrival {
+    Constant,
+    Poisson,
+    Bursty,
+}
+
+impl Arrival {
+    fn name(&self) -> &'static str {
+        match self {
+            Arrival::Constant => "constant",
+            Arrival::Poisson => "poisson",
+            Arrival::Bursty => "bursty",
+        }
+    }
+}
+
+// Send deadlines as offsets from the start of the test. They are absolute, so
+// a late exchange does not push back the messages after it. Each send still
+// waits for the previous reply, once the round trip outgrows the gap the
+// achieved rate falls short of the target. Bursty arrivals send at the target
+// rate during the on periods and nothing during the off ones.
+struct Schedule {
+    arrival: Arrival,
+    gap: f64,
+    on: f64,
+    off: f64,
+    next: f64,
+    rng: StdRng,
+}
+
+impl Schedule {
+    fn new(arrival: Arrival, rate: f64, on: f64, off: f64) -> Schedule {
+        Schedule {
+            arrival,
+            gap: 1.0 / rate,
+            on,
+            off,
+            next: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        let at = self.next;
+        self.next += match self.arrival {
+            Arrival::Constant | Arrival::Bursty => self.gap,
+            // Exponential inter-arrival times
+            Arrival::Poisson => -(1.0 - self.rng.gen::<f64>()).ln() * self.gap,
+        };
+        if let Arrival::Bursty = self.arrival {
+            let period = self.on + self.off;
+            let phase = self.next % period;
+            if phase >= self.on {
+                self.next += period - phase;
+            }
+        }
+        Duration::from_secs_f64(at)
+    }
+}
+
+// Rates and times in seconds, which have to be positive
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok(x),
+        Ok(_) => Err(format!("{}: has to be positive", value)),
+        Err(e) => Err(format!("{:?}: {}", value, e)),
+    }
+}
+
+type Reader = Box<dyn Read + Send>;
+type Writer = Box<dyn Write + Send>;
+
+// The pong lives next to this binary and gets its end of the channel
+// as stdin (requests) and stdout (replies).
+fn spawn_pong(kind: Kind, size: usize, stdin: OwnedFd, stdout: OwnedFd) -> io::Result<Child> {
+    Command::new(env::current_exe()?.with_file_name(PONG))
+        .arg(kind.name())
+        .arg(size.to_string())
+        .stdin(Stdio::from(stdin))
+        .stdout(Stdio::from(stdout))
+        .spawn()
+}
+
+fn connect(kind: Kind, size: usize) -> io::Result<(Child, Reader, Writer)> {
+    match kind {
+        Kind::Socketpair => {
+            let (local, remote) = UnixStream::pair()?;
+            let pong = spawn_pong(kind, size, remote.try_clone()?.into(), remote.into())?;
+            Ok((pong, Box::new(local.try_clone()?), Box::new(local)))
+        }
+        Kind::Pipe => {
+            let (ping_rx, pong_tx) = io::pipe()?;
+            let (pong_rx, ping_tx) = io::pipe()?;
+            let pong = spawn_pong(kind, size, pong_rx.into(), pong_tx.into())?;
+            let rx = File::from(OwnedFd::from(ping_rx));
+            let tx = File::from(OwnedFd::from(ping_tx));
+            Ok((pong, Box::new(rx), Box::new(tx)))
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_wait(
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    pacing: Pacing,
+    mut schedule: Option<Schedule>,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut pong, mut read_stream, mut write_stream) = connect(kind, size)?;
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+
+    let mut samples = Vec::with_capacity(MAX_SAMPLES);
+    // With a target rate the rate column holds the mean interval between sends
+    let interval = schedule.as_ref().map_or(interval, |s| s.gap);
+    let arrival = schedule.as_ref().map_or("interval", |s| s.arrival.name());
+    let start = Instant::now();
+
+    let pacer = Pacer::new(pacing)?;
+    while flag.load(Relaxed) {
+        let deadline = match schedule.as_mut() {
+            Some(schedule) => {
+                let deadline = start + schedule.next();
+                pacer.until(deadline);
+                deadline
+            }
+            None => Instant::now(),
+        };
+        let count_bytes: [u8; 8] = count.to_le_bytes();
+        payload[0..8].copy_from_slice(&count_bytes);
+        let now = Instant::now();
+
+        write_stream.write_all(&payload).unwrap();
+        read_stream.read_exact(&mut payload).unwrap();
+
+        let done = Instant::now();
+        let elapsed = done - now;
+        // On a --rate schedule, a send that leaves after its deadline waited on
+        // the previous replies and the response time counts that wait too
+        // (coordinated omission). Interval pacing has no deadline to miss.
+        samples.push((count, elapsed, deadline - start, done - deadline));
+
+        if schedule.is_none() {
+            pacer.sleep(Duration::from_secs_f64(interval));
+        }
+        count = count.wrapping_add(1);
+    }
+    let achieved = count as f64 / start.elapsed().as_secs_f64();
+
+    pong.kill()?;
+    pong.wait()?;
+
+    for (count, s, intended, r) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing
+            println!(
+                "std,{},rtt,{},{},{},{},{},ns,unix,{},{},{}",
+                kind.transport(),
+                count,
+                interval,
+                payload.len(),
+                tasks,
+                s.as_nanos(),
+                intended.as_nanos(),
+                arrival,
+                pacing.name()
+            );
+            // Interval pacing sends once the previous reply is back, the
+            // response time would only repeat the round trip
+            if schedule.is_some() {
+                println!(
+                    "std,{},response,{},{},{},{},{},ns,unix,{},{},{}",
+                    kind.transport(),
+                    count,
+                    interval,
+                    payload.len(),
+                    tasks,
+                    r.as_nanos(),
+                    intended.as_nanos(),
+                    arrival,
+                    pacing.name()
+                );
+            }
+        } else {
+            println!(
+                "{} bytes: seq={} time={:?} response={:?}",
+                payload.len(),
+                count,
+                s,
+                r
+            );
+        }
+    }
+
+    // Late replies hold back the sends after them, the achieved rate shows
+    // whether the loop kept up with the target
+    if schedule.is_some() {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing
+            println!(
+                "std,{},achieved,{},{},{},{},{:.1},msg/s,unix,,{},{}",
+                kind.transport(),
+                count,
+                interval,
+                payload.len(),
+                tasks,
+                achieved,
+                arrival,
+                pacing.name()
+            );
+        } else {
+            println!("achieved={:.1} msg/s", achieved);
+        }
+    }
+
+    Ok(())
+}
+
+// Keeps `depth` requests in flight: a new one leaves as soon as a reply comes
+// back, so the pipeline paces itself and the interval is not used.
+fn run_depth(
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    depth: usize,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut pong, mut read_stream, mut write_stream) = connect(kind, size)?;
+    // Requests in flight, in the order the pong echoes them, and one credit
+    // per free slot of the pipeline
+    let (in_flight, echoes) = mpsc::channel::<(u64, Instant)>();
+    let (credit, credits) = mpsc::sync_channel::<()>(depth);
+    for _ in 0..depth {
+        credit.try_send(()).unwrap();
+    }
+
+    // Replies are read while the requests go out: once the pipeline outgrows
+    // the socket buffers, both sides would otherwise block on their writes
+    let reader = thread::spawn(move || {
+        let mut payload = vec![0u8; size];
+        let mut count_bytes = [0u8; 8];
+        let mut samples = Vec::with_capacity(MAX_SAMPLES);
+        let mut lost: u64 = 0;
+        while let Ok((c, instant)) = echoes.recv() {
+            read_stream.read_exact(&mut payload).unwrap();
+            count_bytes.copy_from_slice(&payload[0..8]);
+            if u64::from_le_bytes(count_bytes) == c {
+                samples.push((c, instant.elapsed()));
+            } else {
+                // The stream is ordered, an echo out of sequence means
+                // replies went missing
+                lost += 1;
+            }
+            credit.try_send(()).unwrap();
+        }
+        (samples, lost)
+    });
+
+    // Top the pipeline up until the test is over, the reader drains it
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+    while flag.load(Relaxed) {
+        credits.recv().unwrap();
+        payload[0..8].copy_from_slice(&count.to_le_bytes());
+        in_flight.send((count, Instant::now())).unwrap();
+        write_stream.write_all(&payload).unwrap();
+        count = count.wrapping_add(1);
+    }
+    drop(in_flight);
+    let (samples, lost) = reader.join().unwrap();
+
+    pong.kill()?;
+    pong.wait()?;
+
+    for (c, s) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+            println!(
+                "std,{},rtt,{},{},{},{},{},ns,unix,{}",
+                kind.transport(),
+                c,
+                interval,
+                size,
+                tasks,
+                s.as_nanos(),
+                depth
+            );
+        } else {
+            println!("{} bytes: seq={} depth={} time={:?}", size, c, depth, s);
+        }
+    }
+
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+        println!(
+            "std,{},lost,{},{},{},{},{},msgs,unix,{}",
+            kind.transport(),
+            count,
+            interval,
+            size,
+            tasks,
+            lost,
+            depth
+        );
+    } else {
+        println!("lost={}", lost);
+    }
+    Ok(())
+}
+
+fn run(
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    pacing: Pacing,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (_pong, mut read_stream, mut write_stream) = connect(kind, size)?;
+    let pending = Arc::new(Mutex::new(HashMap::<u64, Instant>::new()));
+
+    let c_pending = pending.clone();
+    thread::spawn(move || {
+        let mut payload = vec![0u8; size];
+        let mut count_bytes = [0u8; 8];
+        loop {
+            read_stream.read_exact(&mut payload).unwrap();
+            count_bytes.copy_from_slice(&payload[0..8]);
+            let count = u64::from_le_bytes(count_bytes);
+
+            let instant = c_pending.lock().unwrap().remove(&count).unwrap();
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, pacing
+                println!(
+                    "std,{},rtt,{},{},{},{},{},ns,unix,{}",
+                    kind.transport(),
+                    count,
+                    interval,
+                    payload.len(),
+                    tasks,
+                    instant.elapsed().as_nanos(),
+                    pacing.name()
+                );
+            } else {
+                println!(
+                    "{} bytes: seq={} time={:?}",
+                    payload.len(),
+                    count,
+                    instant.elapsed()
+                );
+            }
+        }
+    });
+
+    //Perform RUN tests
+    let mut count: u64 = 0;
+    let pacer = Pacer::new(pacing)?;
+    loop {
+        let mut payload = vec![0u8; size];
+        let count_bytes: [u8; 8] = count.to_le_bytes();
+        payload[0..8].copy_from_slice(&count_bytes);
+
+        pending.lock().unwrap().insert(count, Instant::now());
+        write_stream.write_all(&payload).unwrap();
+
+        pacer.sleep(Duration::from_secs_f64(interval));
+        count = count.wrapping_add(1);
+    }
+}
+
+fn background(load: Load) {
+    let mut x: usize = 1;
+    match load {
+        Load::Sleep => loop {
+            x = x.wrapping_mul(2);
+            thread::sleep(Duration::from_millis(1));
+        },
+        Load::Spin => loop {
+            x = std::hint::black_box(x.wrapping_mul(2));
+        },
+        Load::Yield => loop {
+            thread::yield_now();
+        },
+        // Threads can only block, so this is the sleep profile without the work
+        Load::Block => loop {
+            thread::sleep(Duration::from_millis(1));
+        },
+        Load::Io => {
+            let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.connect(socket.local_addr().unwrap()).unwrap();
+            let mut buf = [0u8; 64];
+            loop {
+                socket.send(&buf).unwrap();
+                socket.recv(&mut buf).unwrap();
+            }
+        }
+        Load::Channel => unreachable!("Channel tasks are spawned as a ring"),
+    }
+}
+
+fn spawn_load(load: Load, count: usize) {
+    if let Load::Channel = load {
+        // Every task forwards the tokens it gets to the next one
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..count)
+            .map(|_| std::sync::mpsc::sync_channel::<u64>(1))
+            .unzip();
+        for tx in &txs {
+            tx.try_send(0).unwrap();
+        }
+        for (i, rx) in rxs.into_iter().enumerate() {
+            let tx = txs[(i + 1) % count].clone();
+            thread::spawn(move || {
+                while let Ok(token) = rx.recv() {
+                    if tx.send(token).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        return;
+    }
+    for _ in 0..count {
+        thread::spawn(move || background(load));
+    }
+}
+
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    // Only the request/reply loop follows a target rate
+    if args.rate.is_some() && args.depth > 0 {
+        return Err(conflict("--rate does not combine with --depth"));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+    let tasks = Tasks {
+        load: args.load,
+        count: args.spawn,
+    };
+    let flag = Arc::new(AtomicBool::new(true));
+
+    spawn_load(args.load, args.spawn);
+
+    if !args.wait && args.rate.is_none() && args.depth == 0 {
+        run(
+            args.kind,
+            args.size,
+            args.interval,
+            args.csv,
+            tasks,
+            args.pacing,
+        )
+        .unwrap();
+    }
+
+    let c_duration = args.duration;
+    let c_flag = flag.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(c_duration));
+        c_flag.store(false, Relaxed);
+    });
+
+    if args.depth > 0 {
+        run_depth(
+            args.kind,
+            args.size,
+            args.interval,
+            args.csv,
+            tasks,
+            args.depth,
+            flag,
+        )
+        .unwrap();
+    } else {
+        run_wait(
+            args.kind,
+            args.size,
+            args.interval,
+            args.csv,
+            tasks,
+            args.pacing,
+            args.rate
+                .map(|rate| Schedule::new(args.arrival, rate, args.on, args.off)),
+            flag,
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::io::AsFd;
+use std::os::unix::net::UnixStream;
+
+fn run(kind: &str, size: usize) -> Result<(), Box<dyn std::error::Error>> {
+    // The ping hands us the channel as stdin and stdout.
+    let stdin = io::stdin().as_fd().try_clone_to_owned()?;
+    let stdout = io::stdout().as_fd().try_clone_to_owned()?;
+
+    let (mut reader, mut writer): (Box<dyn Read>, Box<dyn Write>) = match kind {
+        "socketpair" => {
+            let stream = UnixStream::from(stdin);
+            (Box::new(stream.try_clone()?), Box::new(stream))
+        }
+        "pipe" => (Box::new(File::from(stdin)), Box::new(File::from(stdout))),
+        _ => return Err(format!("Unknown IPC kind: {}", kind).into()),
+    };
+
+    let mut buf = vec![0u8; size];
+    loop {
+        match reader.read_exact(&mut buf) {
+            Ok(_) => writer.write_all(&buf)?,
+            // The ping closed its end of the channel
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn main() {
+    let kind: String = env::args()
+        .nth(1)
+        .expect("First argument must be the IPC kind (socketpair or pipe)");
+    let size: usize = env::args()
+        .nth(2)
+        .unwrap()
+        .parse()
+        .expect("Second argument must be the buffer size");
+
+    run(&kind, size).unwrap();
+}
@@ -1,43 +1,457 @@
-use std::env;
-use std::net::SocketAddr;
+use clap::{Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::ErrorKind;
 use std::net::UdpSocket;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::io::AsRawFd;
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-fn read_exact(
-    socket: &Arc<UdpSocket>,
-    buffer: &mut [u8],
-) -> Result<SocketAddr, Box<dyn std::error::Error>> {
-    let mut read: usize = 0;
-    let mut g_addr = "127.0.0.1:8080".parse().unwrap();
-    while read < buffer.len() {
-        let (n, addr) = socket.recv_from(&mut buffer[read..])?;
-        g_addr = addr;
-        read += n;
+#[derive(Parser, Debug)]
+struct Args {
+    address: SocketAddr,
+    size: usize,
+    #[clap(long)]
+    ipv6_only: bool,
+    // The multicast subscriber only listens
+    #[clap(long, conflicts_with_all = &["busy-poll", "so-busy-poll", "per-core"])]
+    join: Option<IpAddr>,
+    #[clap(long, default_value = "0.0.0.0")]
+    interface: Ipv4Addr,
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    idle: u64,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(long, value_enum, default_value = "none")]
+    work: Work,
+    #[clap(long, default_value = "0", value_parser = parse_service)]
+    service: f64,
+    #[clap(long, value_enum, default_value = "fixed")]
+    distribution: Distribution,
+    #[clap(long, default_value = "1")]
+    sigma: f64,
+    #[clap(long)]
+    busy_poll: bool,
+    #[clap(long, default_value = "0")]
+    so_busy_poll: u32,
+    #[clap(long)]
+    per_core: bool,
+}
+
+// How the pong runs. It goes in the framework column, plain std for the one
+// thread serving every peer.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    per_core: Option<usize>,
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "std")?;
+        if let Some(n) = self.per_core {
+            write!(f, "/per-core={}", n)?;
+        }
+        Ok(())
+    }
+}
+
+// What the pong does before answering a request: nothing, keep the CPU busy,
+// block the thread it runs on, block a thread of the blocking pool, or await
+// a timer.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Work {
+    None,
+    Spin,
+    Block,
+    Offload,
+    Timer,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Distribution {
+    Fixed,
+    Exponential,
+    Lognormal,
+}
+
+// Service times are `mean` microseconds on average, `sigma` is the spread of
+// the lognormal ones.
+#[derive(Clone, Copy, Debug)]
+struct Service {
+    work: Work,
+    distribution: Distribution,
+    mean: f64,
+    sigma: f64,
+}
+
+impl Service {
+    fn duration(&self, rng: &mut StdRng) -> Duration {
+        let micros = match self.distribution {
+            Distribution::Fixed => self.mean,
+            Distribution::Exponential => -(1.0 - rng.gen::<f64>()).ln() * self.mean,
+            // Box-Muller, with the location shifted for the mean to stay `mean`
+            Distribution::Lognormal => {
+                let z = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * rng.gen::<f64>()).cos();
+                (self.mean.ln() - self.sigma * self.sigma / 2.0 + self.sigma * z).exp()
+            }
+        };
+        Duration::from_secs_f64(micros / 1e6)
     }
-    Ok(g_addr)
 }
 
-fn run(addr: SocketAddr, size: usize) -> Result<(), Box<dyn std::error::Error>> {
-    let socket = Arc::new(UdpSocket::bind(addr)?);
+// Service times are waited out, they cannot be negative
+fn parse_service(mean: &str) -> Result<f64, String> {
+    match mean.parse::<f64>() {
+        Ok(micros) if micros >= 0.0 && micros.is_finite() => Ok(micros),
+        Ok(_) => Err(format!("{}: the service time has to be 0 or more", mean)),
+        Err(e) => Err(format!("{:?}: {}", mean, e)),
+    }
+}
+
+// How the sockets wait for data: parked on the runtime's reactor or busy
+// polled, and the SO_BUSY_POLL budget in microseconds, 0 is off.
+#[derive(Clone, Copy, Debug)]
+struct Poll {
+    busy: bool,
+    budget: u32,
+}
+
+// SO_BUSY_POLL: blocking reads and epoll waits on the socket poll the device
+// queue for up to `micros` before going to sleep.
+fn set_busy_poll(socket: &impl AsRawFd, micros: u32) -> std::io::Result<()> {
+    let value = micros as libc::c_int;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BUSY_POLL,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// CPU time of the whole process, every thread included
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+// Busy polling retries a non-blocking call until it goes through instead of
+// sleeping in the kernel until the socket is ready.
+fn spin<R>(mut op: impl FnMut() -> std::io::Result<R>) -> std::io::Result<R> {
+    loop {
+        match op() {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => std::hint::spin_loop(),
+            res => return res,
+        }
+    }
+}
+
+fn process(service: &Service, rng: &mut StdRng) {
+    if let Work::None = service.work {
+        return;
+    }
+    let duration = service.duration(rng);
+    match service.work {
+        Work::None => {}
+        Work::Spin => {
+            let start = Instant::now();
+            while start.elapsed() < duration {
+                std::hint::spin_loop();
+            }
+        }
+        // Stalls whatever else runs on this thread
+        Work::Block => thread::sleep(duration),
+        // run hands it to a thread of its own instead
+        Work::Offload => unreachable!(),
+        // Threads can only wait by blocking
+        Work::Timer => thread::sleep(duration),
+    }
+}
+
+// SO_REUSEPORT: sockets bound to the same port share it, the kernel spreads
+// the connections or datagrams among them by a hash of the source.
+fn set_reuse_port(socket: &impl AsRawFd) -> std::io::Result<()> {
+    let value: libc::c_int = 1;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// The cores the process may run on, as taskset left them
+fn cores() -> std::io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize)
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+        .collect())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(0, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Thread per core: `serve` runs again on a thread pinned to each of `cores`,
+// nothing is shared between them but the port.
+fn per_core(cores: &[usize], serve: impl Fn() + Sync) {
+    thread::scope(|scope| {
+        for &core in cores {
+            let serve = &serve;
+            thread::Builder::new()
+                .name(format!("pong-{}", core))
+                .spawn_scoped(scope, move || {
+                    pin(core).unwrap();
+                    serve();
+                })
+                .unwrap();
+        }
+    });
+}
+
+// IPv6 sockets are dual-stack unless `ipv6_only` is set.
+fn bind(addr: SocketAddr, ipv6_only: bool, reuse_port: bool) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
+    if reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+// All the receivers of a group share its port.
+fn join(addr: SocketAddr, group: IpAddr, interface: Ipv4Addr) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    match group {
+        IpAddr::V4(group) => socket.join_multicast_v4(&group, &interface)?,
+        IpAddr::V6(group) => socket.join_multicast_v6(&group, 0)?,
+    }
+    Ok(socket.into())
+}
+
+// What the pong knows about each of its clients
+struct Peer {
+    datagrams: u64,
+    bytes: u64,
+    since: Instant,
+    last: Instant,
+    cpu: Duration,
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+fn report(peer: SocketAddr, state: &Peer, size: usize, csv: bool, runtime: RuntimeConfig) {
+    // CPU use of the whole pong since the peer showed up
+    let usage = (cpu_time() - state.cpu).as_secs_f64() / state.since.elapsed().as_secs_f64();
+    if csv {
+        // No load tasks run in the pong, its tasks read like those of an idle ping
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer
+        println!(
+            "{},udp,session,{},0,{},sleep:0,{},bytes,{},{}",
+            runtime,
+            state.datagrams,
+            size,
+            state.bytes,
+            family(peer),
+            peer
+        );
+        println!(
+            "{},udp,cpu,{},0,{},sleep:0,{:.1},%,{},{}",
+            runtime,
+            state.datagrams,
+            size,
+            usage * 100.0,
+            family(peer),
+            peer
+        );
+    } else {
+        println!(
+            "{}: datagrams={} bytes={} active={:?} cpu={:.1}%",
+            peer,
+            state.datagrams,
+            state.bytes,
+            state.last - state.since,
+            usage * 100.0
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run(
+    addr: SocketAddr,
+    size: usize,
+    ipv6_only: bool,
+    reuse_port: bool,
+    idle: Duration,
+    csv: bool,
+    service: Service,
+    poll: Poll,
+    runtime: RuntimeConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = bind(addr, ipv6_only, reuse_port)?;
+    // Wake up at least once per idle period to expire peers
+    socket.set_read_timeout(Some(idle))?;
+    // Busy polling spins through the loop, expiring peers as it goes
+    socket.set_nonblocking(poll.busy)?;
+    if poll.budget > 0 {
+        set_busy_poll(&socket, poll.budget)?;
+    }
+    let socket = Arc::new(socket);
     let mut buf = vec![0u8; size];
+    let mut peers = HashMap::<SocketAddr, Peer>::new();
+    let mut rng = StdRng::from_entropy();
+    let mut sweep = Instant::now();
 
     loop {
-        let addr = read_exact(&socket, &mut buf)?;
-        socket.send_to(&buf, addr)?;
+        match socket.recv_from(&mut buf) {
+            Ok((n, peer)) => {
+                if let Work::Offload = service.work {
+                    // There is no blocking pool, a thread of its own per
+                    // datagram stands in for it and answers once done
+                    let duration = service.duration(&mut rng);
+                    let (socket, datagram) = (socket.clone(), buf[..n].to_vec());
+                    thread::spawn(move || {
+                        thread::sleep(duration);
+                        // Lost like any datagram that cannot leave
+                        let _ = spin(|| socket.send_to(&datagram, peer));
+                    });
+                } else {
+                    process(&service, &mut rng);
+                    // Every datagram goes back to whoever sent it
+                    spin(|| socket.send_to(&buf[..n], peer))?;
+                }
+                let now = Instant::now();
+                let state = peers.entry(peer).or_insert_with(|| Peer {
+                    datagrams: 0,
+                    bytes: 0,
+                    since: now,
+                    last: now,
+                    cpu: cpu_time(),
+                });
+                state.datagrams += 1;
+                state.bytes += n as u64;
+                state.last = now;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        // Peers that went quiet are done, report what they did
+        if sweep.elapsed() >= idle {
+            peers.retain(|peer, state| {
+                let alive = state.last.elapsed() < idle;
+                if !alive {
+                    report(*peer, state, size, csv, runtime);
+                }
+                alive
+            });
+            sweep = Instant::now();
+        }
+    }
+}
+
+fn run_multicast(
+    addr: SocketAddr,
+    size: usize,
+    group: IpAddr,
+    interface: Ipv4Addr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = join(addr, group, interface)?;
+    // Reply from a port of our own so that the ping can tell receivers apart
+    let unspecified: IpAddr = match addr {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let reply = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+
+    let mut buf = vec![0u8; size];
+
+    loop {
+        let (n, addr) = socket.recv_from(&mut buf)?;
+        reply.send_to(&buf[..n], addr)?;
     }
 }
 
 fn main() {
-    let addr: SocketAddr = env::args()
-        .nth(1)
-        .unwrap()
-        .parse()
-        .expect("First argument must be a valid socket address");
-    let size: usize = env::args()
-        .nth(2)
-        .unwrap()
-        .parse()
-        .expect("Second argument must be the buffer size");
-
-    run(addr, size).unwrap();
+    let args = Args::parse();
+    let service = Service {
+        work: args.work,
+        distribution: args.distribution,
+        mean: args.service,
+        sigma: args.sigma,
+    };
+    let poll = Poll {
+        busy: args.busy_poll,
+        budget: args.so_busy_poll,
+    };
+
+    // Thread per core: a thread and a socket on each core taskset left us
+    let cores = args.per_core.then(|| cores().unwrap());
+    let reuse_port = cores.is_some();
+    let runtime = RuntimeConfig {
+        per_core: cores.as_ref().map(Vec::len),
+    };
+
+    // All of it on this thread, or again on each core with --per-core
+    let serve = || match args.join {
+        Some(group) => run_multicast(args.address, args.size, group, args.interface).unwrap(),
+        None => run(
+            args.address,
+            args.size,
+            args.ipv6_only,
+            reuse_port,
+            Duration::from_secs(args.idle),
+            args.csv,
+            service,
+            poll,
+            runtime,
+        )
+        .unwrap(),
+    };
+    match cores {
+        Some(cores) => per_core(&cores, serve),
+        None => serve(),
+    }
 }
@@ -0,0 +1,145 @@
+use clap::{Parser, ValueEnum};
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Sequence number announcing the end of the test to the subscribers
+const END: u64 = u64::MAX;
+// seq, send time, interval
+const HEADER: usize = 24;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Tcp,
+    Udp,
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    address: SocketAddr,
+    // Room for the header at least
+    #[clap(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(HEADER as u64..))]
+    size: usize,
+    interval: f64,
+    #[clap(short = 'n', long, default_value = "1")]
+    subscribers: usize,
+    #[clap(short, long, default_value = "60")]
+    duration: u64,
+}
+
+// Instants cannot be compared across processes, CLOCK_MONOTONIC can.
+fn monotonic() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+fn stamp(payload: &mut [u8], count: u64, interval: f64) {
+    payload[0..8].copy_from_slice(&count.to_le_bytes());
+    payload[8..16].copy_from_slice(&monotonic().to_le_bytes());
+    payload[16..24].copy_from_slice(&interval.to_le_bytes());
+}
+
+fn run_tcp(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    subscribers: usize,
+    duration: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(address)?;
+    let mut streams = Vec::with_capacity(subscribers);
+    while streams.len() < subscribers {
+        let (stream, _src) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        streams.push(stream);
+    }
+
+    // The test starts once all the subscribers are there
+    let start = Instant::now();
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+    while start.elapsed() < duration {
+        stamp(&mut payload, count, interval);
+        for stream in streams.iter_mut() {
+            stream.write_all(&payload)?;
+        }
+
+        thread::sleep(Duration::from_secs_f64(interval));
+        count = count.wrapping_add(1);
+    }
+
+    stamp(&mut payload, END, interval);
+    for stream in streams.iter_mut() {
+        stream.write_all(&payload)?;
+    }
+    Ok(())
+}
+
+fn run_udp(
+    address: SocketAddr,
+    size: usize,
+    interval: f64,
+    subscribers: usize,
+    duration: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::bind(address)?;
+    let mut payload = vec![0u8; size];
+
+    // Subscribers announce themselves with an empty datagram
+    let mut peers = Vec::with_capacity(subscribers);
+    while peers.len() < subscribers {
+        let (_, peer) = socket.recv_from(&mut payload)?;
+        if !peers.contains(&peer) {
+            peers.push(peer);
+        }
+    }
+
+    let start = Instant::now();
+    let mut count: u64 = 0;
+    while start.elapsed() < duration {
+        stamp(&mut payload, count, interval);
+        for peer in peers.iter() {
+            socket.send_to(&payload, peer)?;
+        }
+
+        thread::sleep(Duration::from_secs_f64(interval));
+        count = count.wrapping_add(1);
+    }
+
+    stamp(&mut payload, END, interval);
+    for peer in peers.iter() {
+        socket.send_to(&payload, peer)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let duration = Duration::from_secs(args.duration);
+    match args.kind {
+        Kind::Tcp => run_tcp(
+            args.address,
+            args.size,
+            args.interval,
+            args.subscribers,
+            duration,
+        )
+        .unwrap(),
+        Kind::Udp => run_udp(
+            args.address,
+            args.size,
+            args.interval,
+            args.subscribers,
+            duration,
+        )
+        .unwrap(),
+    }
+}
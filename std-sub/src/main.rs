@@ -0,0 +1,266 @@
+use clap::{Parser, ValueEnum};
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+// Sequence number announcing the end of the test to the subscribers
+const END: u64 = u64::MAX;
+// seq, send time, interval
+const HEADER: usize = 24;
+const PERCENTILES: [(&str, f64); 5] = [
+    ("p50", 0.5),
+    ("p90", 0.9),
+    ("p99", 0.99),
+    ("p999", 0.999),
+    ("max", 1.0),
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Tcp,
+    Udp,
+}
+
+impl Kind {
+    fn transport(&self) -> &'static str {
+        match self {
+            Kind::Tcp => "tcp",
+            Kind::Udp => "udp",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    address: SocketAddr,
+    // Room for the header at least
+    #[clap(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(HEADER as u64..))]
+    size: usize,
+    #[clap(short = 'n', long, default_value = "1")]
+    subscribers: usize,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(short, long, default_value = "5")]
+    timeout: u64,
+}
+
+// One-way latencies (seq, ns) seen by a subscriber, and the publication interval
+struct Samples {
+    interval: f64,
+    latencies: Vec<(u64, u64)>,
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+// Instants cannot be compared across processes, CLOCK_MONOTONIC can.
+fn monotonic() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+// Returns false once the publisher is done.
+fn record(payload: &[u8], samples: &mut Samples) -> bool {
+    let now = monotonic();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&payload[0..8]);
+    let count = u64::from_le_bytes(bytes);
+    if count == END {
+        return false;
+    }
+    bytes.copy_from_slice(&payload[8..16]);
+    let sent = u64::from_le_bytes(bytes);
+    bytes.copy_from_slice(&payload[16..24]);
+    samples.interval = f64::from_le_bytes(bytes);
+    samples.latencies.push((count, now.saturating_sub(sent)));
+    true
+}
+
+fn subscribe_tcp(address: SocketAddr, size: usize) -> io::Result<Samples> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    let mut payload = vec![0u8; size];
+    let mut samples = Samples {
+        interval: 0.0,
+        latencies: Vec::new(),
+    };
+
+    loop {
+        match stream.read_exact(&mut payload) {
+            Ok(_) => {
+                if !record(&payload, &mut samples) {
+                    return Ok(samples);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(samples),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn subscribe_udp(address: SocketAddr, size: usize, timeout: Duration) -> io::Result<Samples> {
+    let unspecified: IpAddr = match address {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+    socket.connect(address)?;
+    socket.send(&[])?;
+    let mut payload = vec![0u8; size];
+    let mut samples = Samples {
+        interval: 0.0,
+        latencies: Vec::new(),
+    };
+
+    // A lost end of test must not hang the subscriber
+    socket.set_read_timeout(Some(timeout))?;
+    loop {
+        match socket.recv(&mut payload) {
+            Ok(_) => {
+                if !record(&payload, &mut samples) {
+                    break;
+                }
+            }
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                break
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(samples)
+}
+
+fn percentile(sorted: &[u64], q: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let i = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[i - 1]
+}
+
+fn report(kind: Kind, family: &str, size: usize, subscribers: Vec<Samples>, csv: bool) {
+    let n = subscribers.len();
+    let interval = subscribers.iter().map(|s| s.interval).fold(0.0, f64::max);
+    let mut percentiles = Vec::with_capacity(n);
+
+    for (id, samples) in subscribers.iter().enumerate() {
+        for (count, latency) in samples.latencies.iter() {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+                println!(
+                    "std,{},oneway,{},{},{},0,{},ns,{},{},{}",
+                    kind.transport(),
+                    count,
+                    interval,
+                    size,
+                    latency,
+                    family,
+                    id,
+                    n
+                );
+            } else {
+                println!(
+                    "{} bytes: sub={} seq={} time={:?}",
+                    size,
+                    id,
+                    count,
+                    Duration::from_nanos(*latency)
+                );
+            }
+        }
+
+        let mut sorted: Vec<u64> = samples.latencies.iter().map(|(_, l)| *l).collect();
+        sorted.sort_unstable();
+        let p: Vec<u64> = PERCENTILES
+            .iter()
+            .map(|(_, q)| percentile(&sorted, *q))
+            .collect();
+        for ((name, _), value) in PERCENTILES.iter().zip(p.iter()) {
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+                println!(
+                    "std,{},oneway-{},{},{},{},0,{},ns,{},{},{}",
+                    kind.transport(),
+                    name,
+                    sorted.len(),
+                    interval,
+                    size,
+                    value,
+                    family,
+                    id,
+                    n
+                );
+            } else {
+                println!("sub={} {}={:?}", id, name, Duration::from_nanos(*value));
+            }
+        }
+        percentiles.push(p);
+    }
+
+    // How far the slowest subscriber lags behind the fastest one
+    for (i, (name, _)) in PERCENTILES.iter().enumerate() {
+        let fastest = percentiles.iter().map(|p| p[i]).min().unwrap_or(0);
+        let slowest = percentiles.iter().map(|p| p[i]).max().unwrap_or(0);
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer, subscribers
+            println!(
+                "std,{},spread-{},{},{},{},0,{},ns,{},all,{}",
+                kind.transport(),
+                name,
+                n,
+                interval,
+                size,
+                slowest - fastest,
+                family,
+                n
+            );
+        } else {
+            println!(
+                "spread {}={:?}",
+                name,
+                Duration::from_nanos(slowest - fastest)
+            );
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let timeout = Duration::from_secs(args.timeout);
+    let handles: Vec<_> = (0..args.subscribers)
+        .map(|_| {
+            let (kind, address, size) = (args.kind, args.address, args.size);
+            thread::spawn(move || match kind {
+                Kind::Tcp => subscribe_tcp(address, size),
+                Kind::Udp => subscribe_udp(address, size, timeout),
+            })
+        })
+        .collect();
+
+    let mut subscribers = Vec::with_capacity(handles.len());
+    for handle in handles {
+        subscribers.push(handle.join().unwrap().unwrap());
+    }
+
+    report(
+        args.kind,
+        family(args.address),
+        args.size,
+        subscribers,
+        args.csv,
+    );
+}
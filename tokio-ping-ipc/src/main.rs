@@ -0,0 +1,1098 @@
+use clap::{CommandFactory, Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::*;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::unix::pipe;
+use tokio::net::UnixStream;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc;
+use tokio::sync::Mutex;
+use tokio::time;
+
+const MAX_SAMPLES: usize = 100_000_000;
+const PONG: &str = "tokio-pong-ipc";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Kind {
+    Socketpair,
+    Pipe,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Socketpair => "socketpair",
+            Kind::Pipe => "pipe",
+        }
+    }
+
+    fn transport(&self) -> &'static str {
+        match self {
+            Kind::Socketpair => "unix",
+            Kind::Pipe => "pipe",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(value_enum)]
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    #[clap(short, long)]
+    wait: bool,
+    #[clap(short, long, default_value = "0")]
+    spawn: usize,
+    #[clap(long, value_enum, default_value = "sleep")]
+    load: Load,
+    #[clap(long, value_enum, default_value = "timer")]
+    pacing: Pacing,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(short, long, default_value = "60")]
+    duration: u64,
+    #[clap(long, default_value = "0")]
+    depth: usize,
+    #[clap(long, value_parser = parse_positive)]
+    rate: Option<f64>,
+    #[clap(long, value_enum, default_value = "constant")]
+    arrival: Arrival,
+    #[clap(long, default_value = "0.1", value_parser = parse_positive)]
+    on: f64,
+    #[clap(long, default_value = "0.9", value_parser = parse_positive)]
+    off: f64,
+    #[clap(long, value_enum, default_value = "multi-thread")]
+    flavor: Flavor,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    worker_threads: Option<usize>,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    event_interval: Option<u32>,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    global_queue_interval: Option<u32>,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_blocking_threads: Option<usize>,
+    #[clap(long)]
+    disable_lifo_slot: bool,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// The tokio scheduler: one thread or work stealing across workers
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Flavor {
+    CurrentThread,
+    MultiThread,
+}
+
+// How the tokio runtime is built, the defaults of its Builder when unset. It
+// goes in the framework column, plain tokio for the default runtime.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    flavor: Flavor,
+    worker_threads: Option<usize>,
+    event_interval: Option<u32>,
+    global_queue_interval: Option<u32>,
+    max_blocking_threads: Option<usize>,
+    lifo_slot: bool,
+    pinning: Pinning,
+}
+
+impl RuntimeConfig {
+    fn build(&self) -> std::io::Result<Runtime> {
+        let mut builder = match self.flavor {
+            Flavor::CurrentThread => Builder::new_current_thread(),
+            Flavor::MultiThread => Builder::new_multi_thread(),
+        };
+        builder.enable_all();
+        if let Some(n) = self.worker_threads {
+            builder.worker_threads(n);
+        }
+        if let Some(n) = self.event_interval {
+            builder.event_interval(n);
+        }
+        if let Some(n) = self.global_queue_interval {
+            builder.global_queue_interval(n);
+        }
+        if let Some(n) = self.max_blocking_threads {
+            builder.max_blocking_threads(n);
+        }
+        // Only there with RUSTFLAGS="--cfg tokio_unstable", validate checks it
+        #[cfg(tokio_unstable)]
+        if !self.lifo_slot {
+            builder.disable_lifo_slot();
+        }
+        // Workers only, each the first time it parks: blocking threads never
+        // park on the scheduler and keep the cores of the process
+        let pinning = self.pinning;
+        let pinned = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        // The hook cannot fail, the first error is kept for build to return
+        let failed = std::sync::Arc::new(std::sync::Mutex::new(None));
+        if pinning.cpus.is_some() {
+            let started = std::sync::atomic::AtomicUsize::new(0);
+            let c_pinned = pinned.clone();
+            let c_failed = failed.clone();
+            builder.on_thread_park(move || {
+                thread_local!(static PINNED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) });
+                if !PINNED.with(|pinned| pinned.replace(true)) {
+                    let n = started.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    if let Err(e) = pin(pinning.worker(n).unwrap()) {
+                        c_failed.lock().unwrap().get_or_insert(e);
+                    }
+                    // Counted even when it failed, build would wait forever
+                    c_pinned.fetch_add(1, std::sync::atomic::Ordering::Release);
+                }
+            });
+        }
+        let rt = builder.build()?;
+        // Idle workers park as soon as they start, the test waits for all of
+        // them to be in place
+        if pinning.cpus.is_some() {
+            let workers = rt.metrics().num_workers();
+            while pinned.load(std::sync::atomic::Ordering::Acquire) < workers {
+                std::thread::yield_now();
+            }
+        }
+        if let Some(e) = failed.lock().unwrap().take() {
+            return Err(e);
+        }
+        Ok(rt)
+    }
+
+    // The pong builds the same runtime, its threads stay on the cores
+    // taskset gave the ping
+    fn pong_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Flavor::CurrentThread = self.flavor {
+            args.push("--flavor=current-thread".to_string());
+        }
+        if let Some(n) = self.worker_threads {
+            args.push(format!("--worker-threads={}", n));
+        }
+        if let Some(n) = self.event_interval {
+            args.push(format!("--event-interval={}", n));
+        }
+        if let Some(n) = self.global_queue_interval {
+            args.push(format!("--global-queue-interval={}", n));
+        }
+        if let Some(n) = self.max_blocking_threads {
+            args.push(format!("--max-blocking-threads={}", n));
+        }
+        if !self.lifo_slot {
+            args.push("--disable-lifo-slot".to_string());
+        }
+        args
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tokio")?;
+        if let Flavor::CurrentThread = self.flavor {
+            write!(f, "/current")?;
+        }
+        if let Some(n) = self.worker_threads {
+            write!(f, "/workers={}", n)?;
+        }
+        if let Some(n) = self.event_interval {
+            write!(f, "/event={}", n)?;
+        }
+        if let Some(n) = self.global_queue_interval {
+            write!(f, "/global={}", n)?;
+        }
+        if let Some(n) = self.max_blocking_threads {
+            write!(f, "/blocking={}", n)?;
+        }
+        if !self.lifo_slot {
+            write!(f, "/nolifo")?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// Background tasks competing with the test for the runtime: sleeping, spinning
+// without ever yielding, yielding in a tight loop, passing tokens around a ring
+// of channels, blocking the worker thread, or doing socket I/O of their own.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Load {
+    Sleep,
+    Spin,
+    Yield,
+    Channel,
+    Block,
+    Io,
+}
+
+impl Load {
+    fn name(&self) -> &'static str {
+        match self {
+            Load::Sleep => "sleep",
+            Load::Spin => "spin",
+            Load::Yield => "yield",
+            Load::Channel => "channel",
+            Load::Block => "block",
+            Load::Io => "io",
+        }
+    }
+}
+
+// The tasks column, the load profile and how many tasks run it
+#[derive(Clone, Copy, Debug)]
+struct Tasks {
+    load: Load,
+    count: usize,
+}
+
+impl fmt::Display for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.load.name(), self.count)
+    }
+}
+
+// How the pings wait for their next send: the runtime's own timer, a blocking
+// sleep handed to another thread, spinning on the clock, or a timerfd armed with
+// the absolute deadline and read like any other file descriptor.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Pacing {
+    Timer,
+    Offload,
+    Spin,
+    Timerfd,
+}
+
+impl Pacing {
+    fn name(&self) -> &'static str {
+        match self {
+            Pacing::Timer => "timer",
+            Pacing::Offload => "offload",
+            Pacing::Spin => "spin",
+            Pacing::Timerfd => "timerfd",
+        }
+    }
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+fn timerfd(flags: libc::c_int) -> std::io::Result<OwnedFd> {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags | libc::TFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Arms the timerfd for an absolute deadline, Instant is CLOCK_MONOTONIC on Linux.
+// False if the deadline has passed already, a zero expiry would disarm it.
+fn arm(fd: RawFd, deadline: Instant) -> std::io::Result<bool> {
+    let wait = deadline.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        return Ok(false);
+    }
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    let nanos = now.tv_nsec as u64 + wait.subsec_nanos() as u64;
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: now.tv_sec + (wait.as_secs() + nanos / 1_000_000_000) as libc::time_t,
+            tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+        },
+    };
+    let res =
+        unsafe { libc::timerfd_settime(fd, libc::TFD_TIMER_ABSTIME, &spec, std::ptr::null_mut()) };
+    if res < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+// How many times the timerfd expired since it was armed
+fn expirations(fd: RawFd) -> std::io::Result<u64> {
+    let mut count = [0u8; 8];
+    let n = unsafe { libc::read(fd, count.as_mut_ptr() as *mut libc::c_void, count.len()) };
+    if n < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(u64::from_ne_bytes(count))
+}
+
+// Waits with the chosen pacing, the timerfd is created once and armed again
+// for every deadline.
+struct Pacer {
+    pacing: Pacing,
+    timerfd: Option<AsyncFd<OwnedFd>>,
+}
+
+impl Pacer {
+    fn new(pacing: Pacing) -> std::io::Result<Pacer> {
+        let timerfd = match pacing {
+            Pacing::Timerfd => Some(AsyncFd::new(timerfd(libc::TFD_NONBLOCK)?)?),
+            _ => None,
+        };
+        Ok(Pacer { pacing, timerfd })
+    }
+
+    async fn sleep(&self, wait: Duration) {
+        self.until(Instant::now() + wait).await;
+    }
+
+    async fn until(&self, deadline: Instant) {
+        match self.pacing {
+            Pacing::Timer => time::sleep_until(deadline.into()).await,
+            Pacing::Offload => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                tokio::task::spawn_blocking(move || std::thread::sleep(wait))
+                    .await
+                    .unwrap();
+            }
+            Pacing::Spin => {
+                while Instant::now() < deadline {
+                    std::hint::spin_loop();
+                }
+            }
+            Pacing::Timerfd => {
+                let timerfd = self.timerfd.as_ref().unwrap();
+                if arm(timerfd.as_raw_fd(), deadline).unwrap() {
+                    loop {
+                        let mut guard = timerfd.readable().await.unwrap();
+                        if let Ok(res) = guard.try_io(|fd| expirations(fd.as_raw_fd())) {
+                            res.unwrap();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Arrival {
+    Constant,
+    Poisson,
+    Bursty,
+}
+
+impl Arrival {
+    fn name(&self) -> &'static str {
+        match self {
+            Arrival::Constant => "constant",
+            Arrival::Poisson => "poisson",
+            Arrival::Bursty => "bursty",
+        }
+    }
+}
+
+// Send deadlines as offsets from the start of the test. They are absolute, so
+// a late exchange does not push back the messages after it. Each send still
+// waits for the previous reply, once the round trip outgrows the gap the
+// achieved rate falls short of the target. Bursty arrivals send at the target
+// rate during the on periods and nothing during the off ones.
+struct Schedule {
+    arrival: Arrival,
+    gap: f64,
+    on: f64,
+    off: f64,
+    next: f64,
+    rng: StdRng,
+}
+
+impl Schedule {
+    fn new(arrival: Arrival, rate: f64, on: f64, off: f64) -> Schedule {
+        Schedule {
+            arrival,
+            gap: 1.0 / rate,
+            on,
+            off,
+            next: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        let at = self.next;
+        self.next += match self.arrival {
+            Arrival::Constant | Arrival::Bursty => self.gap,
+            // Exponential inter-arrival times
+            Arrival::Poisson => -(1.0 - self.rng.gen::<f64>()).ln() * self.gap,
+        };
+        if let Arrival::Bursty = self.arrival {
+            let period = self.on + self.off;
+            let phase = self.next % period;
+            if phase >= self.on {
+                self.next += period - phase;
+            }
+        }
+        Duration::from_secs_f64(at)
+    }
+}
+
+// Rates and times in seconds, which have to be positive
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok(x),
+        Ok(_) => Err(format!("{}: has to be positive", value)),
+        Err(e) => Err(format!("{:?}: {}", value, e)),
+    }
+}
+
+type Reader = Box<dyn AsyncRead + Unpin + Send>;
+type Writer = Box<dyn AsyncWrite + Unpin + Send>;
+
+// The pong lives next to this binary and gets its end of the channel
+// as stdin (requests) and stdout (replies).
+fn spawn_pong(
+    kind: Kind,
+    size: usize,
+    runtime: RuntimeConfig,
+    stdin: OwnedFd,
+    stdout: OwnedFd,
+) -> io::Result<Child> {
+    let cores = *PROCESS_CORES.get().unwrap();
+    let mut command = Command::new(env::current_exe()?.with_file_name(PONG));
+    command
+        .arg(kind.name())
+        .arg(size.to_string())
+        .args(runtime.pong_args())
+        .stdin(Stdio::from(stdin))
+        .stdout(Stdio::from(stdout));
+    // Forked from a pinned thread the pong would share its core, it gets the
+    // cores of the process back before it execs
+    unsafe {
+        command.pre_exec(move || {
+            let size = std::mem::size_of::<libc::cpu_set_t>();
+            if libc::sched_setaffinity(0, size, &cores) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let pong = command.spawn()?;
+    // Its mapping goes to the pin log next to the threads of the ping
+    let list: Vec<String> = (0..libc::CPU_SETSIZE as usize)
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &cores) })
+        .map(|core| core.to_string())
+        .collect();
+    eprintln!("{} ({}): cores {}", PONG, pong.id(), list.join(","));
+    Ok(pong)
+}
+
+// The cores taskset gave the process, read in main before any of its threads
+// gets pinned
+static PROCESS_CORES: OnceLock<libc::cpu_set_t> = OnceLock::new();
+
+// The cores the calling thread may run on
+fn affinity() -> io::Result<libc::cpu_set_t> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(set)
+}
+
+fn connect(kind: Kind, size: usize, runtime: RuntimeConfig) -> io::Result<(Child, Reader, Writer)> {
+    match kind {
+        Kind::Socketpair => {
+            let (local, remote) = StdUnixStream::pair()?;
+            let pong = spawn_pong(
+                kind,
+                size,
+                runtime,
+                remote.try_clone()?.into(),
+                remote.into(),
+            )?;
+            local.set_nonblocking(true)?;
+            let (read_stream, write_stream) = UnixStream::from_std(local)?.into_split();
+            Ok((pong, Box::new(read_stream), Box::new(write_stream)))
+        }
+        Kind::Pipe => {
+            let (ping_rx, pong_tx) = io::pipe()?;
+            let (pong_rx, ping_tx) = io::pipe()?;
+            let pong = spawn_pong(kind, size, runtime, pong_rx.into(), pong_tx.into())?;
+            let rx = pipe::Receiver::from_file(File::from(OwnedFd::from(ping_rx)))?;
+            let tx = pipe::Sender::from_file(File::from(OwnedFd::from(ping_tx)))?;
+            Ok((pong, Box::new(rx), Box::new(tx)))
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_wait(
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+    mut schedule: Option<Schedule>,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut pong, mut read_stream, mut write_stream) = connect(kind, size, runtime)?;
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+    let mut samples = Vec::with_capacity(MAX_SAMPLES);
+    // With a target rate the rate column holds the mean interval between sends
+    let interval = schedule.as_ref().map_or(interval, |s| s.gap);
+    let arrival = schedule.as_ref().map_or("interval", |s| s.arrival.name());
+    let start = Instant::now();
+    let pacer = Pacer::new(pacing)?;
+    while flag.load(Relaxed) {
+        let deadline = match schedule.as_mut() {
+            Some(schedule) => {
+                let deadline = start + schedule.next();
+                pacer.until(deadline).await;
+                deadline
+            }
+            None => Instant::now(),
+        };
+        let count_bytes: [u8; 8] = count.to_le_bytes();
+        payload[0..8].copy_from_slice(&count_bytes);
+        let now = Instant::now();
+
+        write_stream.write_all(&payload).await.unwrap();
+        read_stream.read_exact(&mut payload).await.unwrap();
+        let done = Instant::now();
+        let elapsed = done - now;
+        // On a --rate schedule, a send that leaves after its deadline waited on
+        // the previous replies and the response time counts that wait too
+        // (coordinated omission). Interval pacing has no deadline to miss.
+        samples.push((count, elapsed, deadline - start, done - deadline));
+
+        if schedule.is_none() {
+            pacer.sleep(Duration::from_secs_f64(interval)).await;
+        }
+        count = count.wrapping_add(1);
+    }
+    let achieved = count as f64 / start.elapsed().as_secs_f64();
+
+    pong.kill()?;
+    pong.wait()?;
+
+    for (count, s, intended, r) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing
+            println!(
+                "{},{},rtt,{},{},{},{},{},ns,unix,{},{},{}",
+                runtime,
+                kind.transport(),
+                count,
+                interval,
+                payload.len(),
+                tasks,
+                s.as_nanos(),
+                intended.as_nanos(),
+                arrival,
+                pacing.name()
+            );
+            // Interval pacing sends once the previous reply is back, the
+            // response time would only repeat the round trip
+            if schedule.is_some() {
+                println!(
+                    "{},{},response,{},{},{},{},{},ns,unix,{},{},{}",
+                    runtime,
+                    kind.transport(),
+                    count,
+                    interval,
+                    payload.len(),
+                    tasks,
+                    r.as_nanos(),
+                    intended.as_nanos(),
+                    arrival,
+                    pacing.name()
+                );
+            }
+        } else {
+            println!(
+                "{} bytes: seq={} time={:?} response={:?}",
+                payload.len(),
+                count,
+                s,
+                r
+            );
+        }
+    }
+
+    // Late replies hold back the sends after them, the achieved rate shows
+    // whether the loop kept up with the target
+    if schedule.is_some() {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, intended, arrival, pacing
+            println!(
+                "{},{},achieved,{},{},{},{},{:.1},msg/s,unix,,{},{}",
+                runtime,
+                kind.transport(),
+                count,
+                interval,
+                payload.len(),
+                tasks,
+                achieved,
+                arrival,
+                pacing.name()
+            );
+        } else {
+            println!("achieved={:.1} msg/s", achieved);
+        }
+    }
+
+    Ok(())
+}
+
+// Keeps `depth` requests in flight: a new one leaves as soon as a reply comes
+// back, so the pipeline paces itself and the interval is not used.
+#[allow(clippy::too_many_arguments)]
+async fn run_depth(
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    depth: usize,
+    flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut pong, mut read_stream, mut write_stream) = connect(kind, size, runtime)?;
+    // Requests in flight, in the order the pong echoes them, and one credit
+    // per free slot of the pipeline
+    let (in_flight, mut echoes) = mpsc::unbounded_channel::<(u64, Instant)>();
+    let (credit, mut credits) = mpsc::channel::<()>(depth);
+    for _ in 0..depth {
+        credit.try_send(()).unwrap();
+    }
+
+    // Replies are read while the requests go out: once the pipeline outgrows
+    // the socket buffers, both sides would otherwise block on their writes
+    let reader = tokio::task::spawn(async move {
+        let mut payload = vec![0u8; size];
+        let mut count_bytes = [0u8; 8];
+        let mut samples = Vec::with_capacity(MAX_SAMPLES);
+        let mut lost: u64 = 0;
+        while let Some((c, instant)) = echoes.recv().await {
+            read_stream.read_exact(&mut payload).await.unwrap();
+            count_bytes.copy_from_slice(&payload[0..8]);
+            if u64::from_le_bytes(count_bytes) == c {
+                samples.push((c, instant.elapsed()));
+            } else {
+                // The stream is ordered, an echo out of sequence means
+                // replies went missing
+                lost += 1;
+            }
+            credit.try_send(()).unwrap();
+        }
+        (samples, lost)
+    });
+
+    // Top the pipeline up until the test is over, the reader drains it
+    let mut count: u64 = 0;
+    let mut payload = vec![0u8; size];
+    while flag.load(Relaxed) {
+        credits.recv().await.unwrap();
+        payload[0..8].copy_from_slice(&count.to_le_bytes());
+        in_flight.send((count, Instant::now())).unwrap();
+        write_stream.write_all(&payload).await.unwrap();
+        count = count.wrapping_add(1);
+    }
+    drop(in_flight);
+    let (samples, lost) = reader.await?;
+
+    pong.kill()?;
+    pong.wait()?;
+
+    for (c, s) in samples {
+        if csv {
+            // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+            println!(
+                "{},{},rtt,{},{},{},{},{},ns,unix,{}",
+                runtime,
+                kind.transport(),
+                c,
+                interval,
+                size,
+                tasks,
+                s.as_nanos(),
+                depth
+            );
+        } else {
+            println!("{} bytes: seq={} depth={} time={:?}", size, c, depth, s);
+        }
+    }
+
+    if csv {
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, depth
+        println!(
+            "{},{},lost,{},{},{},{},{},msgs,unix,{}",
+            runtime,
+            kind.transport(),
+            count,
+            interval,
+            size,
+            tasks,
+            lost,
+            depth
+        );
+    } else {
+        println!("lost={}", lost);
+    }
+    Ok(())
+}
+
+async fn run(
+    kind: Kind,
+    size: usize,
+    interval: f64,
+    csv: bool,
+    tasks: Tasks,
+    runtime: RuntimeConfig,
+    pacing: Pacing,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (_pong, mut read_stream, mut write_stream) = connect(kind, size, runtime)?;
+    let pending = Arc::new(Mutex::new(HashMap::<u64, Instant>::new()));
+
+    let c_pending = pending.clone();
+    tokio::task::spawn(async move {
+        let mut payload = vec![0u8; size];
+        let mut count_bytes = [0u8; 8];
+        loop {
+            read_stream.read_exact(&mut payload).await.unwrap();
+            count_bytes.copy_from_slice(&payload[0..8]);
+            let count = u64::from_le_bytes(count_bytes);
+
+            let instant = c_pending.lock().await.remove(&count).unwrap();
+
+            if csv {
+                // framework, transport, test, count, rate, payload, tasks, value, unit, family, pacing
+                println!(
+                    "{},{},rtt,{},{},{},{},{},ns,unix,{}",
+                    runtime,
+                    kind.transport(),
+                    count,
+                    interval,
+                    payload.len(),
+                    tasks,
+                    instant.elapsed().as_nanos(),
+                    pacing.name()
+                );
+            } else {
+                println!(
+                    "{} bytes: seq={} time={:?}",
+                    payload.len(),
+                    count,
+                    instant.elapsed()
+                );
+            }
+        }
+    });
+
+    //Perform RUN tests
+    let mut count: u64 = 0;
+    let pacer = Pacer::new(pacing)?;
+    loop {
+        let mut payload = vec![0u8; size];
+        let count_bytes: [u8; 8] = count.to_le_bytes();
+        payload[0..8].copy_from_slice(&count_bytes);
+
+        pending.lock().await.insert(count, Instant::now());
+        write_stream.write_all(&payload).await.unwrap();
+
+        pacer.sleep(Duration::from_secs_f64(interval)).await;
+        count = count.wrapping_add(1);
+    }
+}
+
+async fn background(load: Load) {
+    let mut x: usize = 1;
+    match load {
+        Load::Sleep => loop {
+            x = x.wrapping_mul(2);
+            time::sleep(Duration::from_millis(1)).await;
+        },
+        // Never yields, so it keeps a worker for itself
+        Load::Spin => loop {
+            x = std::hint::black_box(x.wrapping_mul(2));
+        },
+        Load::Yield => loop {
+            tokio::task::yield_now().await;
+        },
+        Load::Block => loop {
+            std::thread::sleep(Duration::from_millis(1));
+            tokio::task::yield_now().await;
+        },
+        Load::Io => {
+            let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.connect(socket.local_addr().unwrap()).await.unwrap();
+            let mut buf = [0u8; 64];
+            loop {
+                socket.send(&buf).await.unwrap();
+                socket.recv(&mut buf).await.unwrap();
+                // The datagram is always there already, which is no reason
+                // to keep the worker
+                tokio::task::yield_now().await;
+            }
+        }
+        Load::Channel => unreachable!("Channel tasks are spawned as a ring"),
+    }
+}
+
+fn spawn_load(load: Load, count: usize) {
+    if let Load::Channel = load {
+        // Every task forwards the tokens it gets to the next one
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..count)
+            .map(|_| tokio::sync::mpsc::channel::<u64>(1))
+            .unzip();
+        for tx in &txs {
+            tx.try_send(0).unwrap();
+        }
+        for (i, mut rx) in rxs.into_iter().enumerate() {
+            let tx = txs[(i + 1) % count].clone();
+            tokio::spawn(async move {
+                while let Some(token) = rx.recv().await {
+                    if tx.send(token).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        return;
+    }
+    for _ in 0..count {
+        tokio::spawn(background(load));
+    }
+}
+
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    // Only the request/reply loop follows a target rate
+    if args.rate.is_some() && args.depth > 0 {
+        return Err(conflict("--rate does not combine with --depth"));
+    }
+    let multi_thread = matches!(args.flavor, Flavor::MultiThread);
+    // The current-thread runtime runs on the main thread
+    if !multi_thread && (args.worker_threads.is_some() || args.disable_lifo_slot) {
+        return Err(conflict(
+            "--worker-threads and --disable-lifo-slot need the multi-thread flavor",
+        ));
+    }
+    if args.disable_lifo_slot && !cfg!(tokio_unstable) {
+        return Err(conflict(
+            "--disable-lifo-slot needs a build with RUSTFLAGS=\"--cfg tokio_unstable\"",
+        ));
+    }
+    if args.reactor_cpu.is_some() {
+        return Err(conflict(
+            "tokio has no reactor thread, its workers take turns on the I/O driver",
+        ));
+    }
+    if args.cpus.is_some() && !multi_thread {
+        return Err(conflict(
+            "--cpus needs the multi-thread flavor, the current-thread runtime runs on the main thread (--main-cpu)",
+        ));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+    PROCESS_CORES.get_or_init(|| affinity().unwrap());
+    let tasks = Tasks {
+        load: args.load,
+        count: args.spawn,
+    };
+
+    let flag = Arc::new(AtomicBool::new(true));
+
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    let runtime = RuntimeConfig {
+        flavor: args.flavor,
+        worker_threads: args.worker_threads,
+        event_interval: args.event_interval,
+        global_queue_interval: args.global_queue_interval,
+        max_blocking_threads: args.max_blocking_threads,
+        lifo_slot: !args.disable_lifo_slot,
+        pinning,
+    };
+
+    let rt = runtime.build().unwrap();
+    runtime.pinning.pin_main();
+    let handle = rt.spawn(async move {
+        spawn_load(args.load, args.spawn);
+
+        if !args.wait && args.rate.is_none() && args.depth == 0 {
+            run(
+                args.kind,
+                args.size,
+                args.interval,
+                args.csv,
+                tasks,
+                runtime,
+                args.pacing,
+            )
+            .await
+            .unwrap();
+        } else {
+            let c_duration = args.duration;
+            let c_flag = flag.clone();
+            tokio::spawn(async move {
+                time::sleep(Duration::from_secs(c_duration)).await;
+                c_flag.store(false, Relaxed);
+            });
+
+            if args.depth > 0 {
+                run_depth(
+                    args.kind,
+                    args.size,
+                    args.interval,
+                    args.csv,
+                    tasks,
+                    runtime,
+                    args.depth,
+                    flag,
+                )
+                .await
+                .unwrap();
+            } else {
+                run_wait(
+                    args.kind,
+                    args.size,
+                    args.interval,
+                    args.csv,
+                    tasks,
+                    runtime,
+                    args.pacing,
+                    args.rate
+                        .map(|rate| Schedule::new(args.arrival, rate, args.on, args.off)),
+                    flag,
+                )
+                .await
+                .unwrap();
+            }
+        }
+    });
+    rt.block_on(handle).unwrap();
+}
@@ -0,0 +1,143 @@
+use clap::{CommandFactory, Parser, ValueEnum};
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsFd;
+use std::os::unix::net::UnixStream as StdUnixStream;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::unix::pipe;
+use tokio::net::UnixStream;
+use tokio::runtime::{Builder, Runtime};
+
+// The ping spawns us with its own runtime flags
+#[derive(Parser, Debug)]
+struct Args {
+    kind: String,
+    size: usize,
+    #[clap(long, value_enum, default_value = "multi-thread")]
+    flavor: Flavor,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    worker_threads: Option<usize>,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    event_interval: Option<u32>,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    global_queue_interval: Option<u32>,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_blocking_threads: Option<usize>,
+    #[clap(long)]
+    disable_lifo_slot: bool,
+}
+
+// The tokio scheduler: one thread or work stealing across workers
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Flavor {
+    CurrentThread,
+    MultiThread,
+}
+
+// How the tokio runtime is built, the defaults of its Builder when unset
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    flavor: Flavor,
+    worker_threads: Option<usize>,
+    event_interval: Option<u32>,
+    global_queue_interval: Option<u32>,
+    max_blocking_threads: Option<usize>,
+    // Only read with tokio_unstable, validate refuses --disable-lifo-slot without it
+    #[cfg_attr(not(tokio_unstable), allow(dead_code))]
+    lifo_slot: bool,
+}
+
+impl RuntimeConfig {
+    fn build(&self) -> std::io::Result<Runtime> {
+        let mut builder = match self.flavor {
+            Flavor::CurrentThread => Builder::new_current_thread(),
+            Flavor::MultiThread => Builder::new_multi_thread(),
+        };
+        builder.enable_all();
+        if let Some(n) = self.worker_threads {
+            builder.worker_threads(n);
+        }
+        if let Some(n) = self.event_interval {
+            builder.event_interval(n);
+        }
+        if let Some(n) = self.global_queue_interval {
+            builder.global_queue_interval(n);
+        }
+        if let Some(n) = self.max_blocking_threads {
+            builder.max_blocking_threads(n);
+        }
+        // Only there with RUSTFLAGS="--cfg tokio_unstable", validate checks it
+        #[cfg(tokio_unstable)]
+        if !self.lifo_slot {
+            builder.disable_lifo_slot();
+        }
+        builder.build()
+    }
+}
+
+async fn run(kind: &str, size: usize) -> Result<(), Box<dyn std::error::Error>> {
+    // The ping hands us the channel as stdin and stdout.
+    let stdin = io::stdin().as_fd().try_clone_to_owned()?;
+    let stdout = io::stdout().as_fd().try_clone_to_owned()?;
+
+    let (mut reader, mut writer): (Box<dyn AsyncRead + Unpin>, Box<dyn AsyncWrite + Unpin>) =
+        match kind {
+            "socketpair" => {
+                let stream = StdUnixStream::from(stdin);
+                stream.set_nonblocking(true)?;
+                let (read_stream, write_stream) = UnixStream::from_std(stream)?.into_split();
+                (Box::new(read_stream), Box::new(write_stream))
+            }
+            "pipe" => (
+                Box::new(pipe::Receiver::from_file(File::from(stdin))?),
+                Box::new(pipe::Sender::from_file(File::from(stdout))?),
+            ),
+            _ => return Err(format!("Unknown IPC kind: {}", kind).into()),
+        };
+
+    let mut buf = vec![0u8; size];
+    loop {
+        match reader.read_exact(&mut buf).await {
+            Ok(_) => writer.write_all(&buf).await?,
+            // The ping closed its end of the channel
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    let multi_thread = matches!(args.flavor, Flavor::MultiThread);
+    // The current-thread runtime runs on the main thread
+    if !multi_thread && (args.worker_threads.is_some() || args.disable_lifo_slot) {
+        return Err(conflict(
+            "--worker-threads and --disable-lifo-slot need the multi-thread flavor",
+        ));
+    }
+    if args.disable_lifo_slot && !cfg!(tokio_unstable) {
+        return Err(conflict(
+            "--disable-lifo-slot needs a build with RUSTFLAGS=\"--cfg tokio_unstable\"",
+        ));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+    let runtime = RuntimeConfig {
+        flavor: args.flavor,
+        worker_threads: args.worker_threads,
+        event_interval: args.event_interval,
+        global_queue_interval: args.global_queue_interval,
+        max_blocking_threads: args.max_blocking_threads,
+        lifo_slot: !args.disable_lifo_slot,
+    };
+
+    let rt = runtime.build().unwrap();
+    rt.block_on(run(&args.kind, args.size)).unwrap();
+}
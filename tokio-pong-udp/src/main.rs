@@ -1,45 +1,751 @@
-use std::env;
-use std::net::SocketAddr;
+use clap::{CommandFactory, Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
+use tokio::runtime::{Builder, Runtime};
+use tokio::task;
+use tokio::time;
 
-async fn read_exact(
-    socket: &Arc<UdpSocket>,
-    buffer: &mut [u8],
-) -> Result<SocketAddr, Box<dyn std::error::Error>> {
-    let mut read: usize = 0;
-    let mut g_addr = "127.0.0.1:8080".parse().unwrap();
-    while read < buffer.len() {
-        let (n, addr) = socket.recv_from(&mut buffer[read..]).await?;
-        g_addr = addr;
-        read += n;
+#[derive(Parser, Debug)]
+struct Args {
+    address: SocketAddr,
+    size: usize,
+    #[clap(long)]
+    ipv6_only: bool,
+    // The multicast subscriber only listens
+    #[clap(long, conflicts_with_all = &["busy-poll", "so-busy-poll", "per-core"])]
+    join: Option<IpAddr>,
+    #[clap(long, default_value = "0.0.0.0")]
+    interface: Ipv4Addr,
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    idle: u64,
+    #[clap(short, long)]
+    csv: bool,
+    #[clap(long, value_enum, default_value = "none")]
+    work: Work,
+    #[clap(long, default_value = "0", value_parser = parse_service)]
+    service: f64,
+    #[clap(long, value_enum, default_value = "fixed")]
+    distribution: Distribution,
+    #[clap(long, default_value = "1")]
+    sigma: f64,
+    #[clap(long)]
+    busy_poll: bool,
+    #[clap(long, default_value = "0")]
+    so_busy_poll: u32,
+    // multi-thread, or current-thread with --per-core
+    #[clap(long, value_enum)]
+    flavor: Option<Flavor>,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    worker_threads: Option<usize>,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    event_interval: Option<u32>,
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    global_queue_interval: Option<u32>,
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_blocking_threads: Option<usize>,
+    #[clap(long)]
+    disable_lifo_slot: bool,
+    // Pins its threads itself, on the cores taskset left us
+    #[clap(long, conflicts_with_all = &["cpus", "main-cpu", "reactor-cpu"])]
+    per_core: bool,
+    #[clap(long, value_parser = parse_cpus)]
+    cpus: Option<&'static [usize]>,
+    #[clap(long, value_parser = parse_core)]
+    main_cpu: Option<usize>,
+    #[clap(long, value_parser = parse_core)]
+    reactor_cpu: Option<usize>,
+}
+
+// The tokio scheduler: one thread or work stealing across workers
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Flavor {
+    CurrentThread,
+    MultiThread,
+}
+
+// How the tokio runtime is built, the defaults of its Builder when unset. It
+// goes in the framework column, plain tokio for the default runtime.
+#[derive(Clone, Copy, Debug)]
+struct RuntimeConfig {
+    flavor: Flavor,
+    worker_threads: Option<usize>,
+    event_interval: Option<u32>,
+    global_queue_interval: Option<u32>,
+    max_blocking_threads: Option<usize>,
+    lifo_slot: bool,
+    per_core: Option<usize>,
+    pinning: Pinning,
+}
+
+impl RuntimeConfig {
+    fn build(&self) -> std::io::Result<Runtime> {
+        let mut builder = match self.flavor {
+            Flavor::CurrentThread => Builder::new_current_thread(),
+            Flavor::MultiThread => Builder::new_multi_thread(),
+        };
+        builder.enable_all();
+        if let Some(n) = self.worker_threads {
+            builder.worker_threads(n);
+        }
+        if let Some(n) = self.event_interval {
+            builder.event_interval(n);
+        }
+        if let Some(n) = self.global_queue_interval {
+            builder.global_queue_interval(n);
+        }
+        if let Some(n) = self.max_blocking_threads {
+            builder.max_blocking_threads(n);
+        }
+        // Only there with RUSTFLAGS="--cfg tokio_unstable", validate checks it
+        #[cfg(tokio_unstable)]
+        if !self.lifo_slot {
+            builder.disable_lifo_slot();
+        }
+        // Workers only, each the first time it parks: blocking threads never
+        // park on the scheduler and keep the cores of the process
+        let pinning = self.pinning;
+        let pinned = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        // The hook cannot fail, the first error is kept for build to return
+        let failed = std::sync::Arc::new(std::sync::Mutex::new(None));
+        if pinning.cpus.is_some() {
+            let started = std::sync::atomic::AtomicUsize::new(0);
+            let c_pinned = pinned.clone();
+            let c_failed = failed.clone();
+            builder.on_thread_park(move || {
+                thread_local!(static PINNED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) });
+                if !PINNED.with(|pinned| pinned.replace(true)) {
+                    let n = started.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    if let Err(e) = pin(pinning.worker(n).unwrap()) {
+                        c_failed.lock().unwrap().get_or_insert(e);
+                    }
+                    // Counted even when it failed, build would wait forever
+                    c_pinned.fetch_add(1, std::sync::atomic::Ordering::Release);
+                }
+            });
+        }
+        let rt = builder.build()?;
+        // Idle workers park as soon as they start, the test waits for all of
+        // them to be in place
+        if pinning.cpus.is_some() {
+            let workers = rt.metrics().num_workers();
+            while pinned.load(std::sync::atomic::Ordering::Acquire) < workers {
+                std::thread::yield_now();
+            }
+        }
+        if let Some(e) = failed.lock().unwrap().take() {
+            return Err(e);
+        }
+        Ok(rt)
+    }
+}
+
+impl fmt::Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tokio")?;
+        if let Flavor::CurrentThread = self.flavor {
+            write!(f, "/current")?;
+        }
+        if let Some(n) = self.worker_threads {
+            write!(f, "/workers={}", n)?;
+        }
+        if let Some(n) = self.event_interval {
+            write!(f, "/event={}", n)?;
+        }
+        if let Some(n) = self.global_queue_interval {
+            write!(f, "/global={}", n)?;
+        }
+        if let Some(n) = self.max_blocking_threads {
+            write!(f, "/blocking={}", n)?;
+        }
+        if !self.lifo_slot {
+            write!(f, "/nolifo")?;
+        }
+        if let Some(n) = self.per_core {
+            write!(f, "/per-core={}", n)?;
+        }
+        write!(f, "{}", self.pinning)?;
+        Ok(())
+    }
+}
+
+// Where the threads run, on top of the cores taskset gave the whole process:
+// the workers round robin over `cpus`, the main thread on `main` and the
+// reactor thread, for the runtimes that have one, on `reactor`. Threads left
+// alone inherit the cores of the thread that started them.
+#[derive(Clone, Copy, Debug)]
+struct Pinning {
+    cpus: Option<&'static [usize]>,
+    main: Option<usize>,
+    reactor: Option<usize>,
+}
+
+impl Pinning {
+    // The core of the `n`th worker to start
+    fn worker(&self, n: usize) -> Option<usize> {
+        self.cpus.map(|cpus| cpus[n % cpus.len()])
+    }
+
+    fn pin_main(&self) {
+        if let Some(core) = self.main {
+            pin(core).unwrap();
+        }
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpus) = self.cpus {
+            let cpus: Vec<String> = cpus.iter().map(|core| core.to_string()).collect();
+            write!(f, "/cpus={}", cpus.join("+"))?;
+        }
+        if let Some(core) = self.main {
+            write!(f, "/main={}", core)?;
+        }
+        if let Some(core) = self.reactor {
+            write!(f, "/reactor={}", core)?;
+        }
+        Ok(())
+    }
+}
+
+// What the pong does before answering a request: nothing, keep the CPU busy,
+// block the thread it runs on, block a thread of the blocking pool, or await
+// a timer.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Work {
+    None,
+    Spin,
+    Block,
+    Offload,
+    Timer,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Distribution {
+    Fixed,
+    Exponential,
+    Lognormal,
+}
+
+// Service times are `mean` microseconds on average, `sigma` is the spread of
+// the lognormal ones.
+#[derive(Clone, Copy, Debug)]
+struct Service {
+    work: Work,
+    distribution: Distribution,
+    mean: f64,
+    sigma: f64,
+}
+
+impl Service {
+    fn duration(&self, rng: &mut StdRng) -> Duration {
+        let micros = match self.distribution {
+            Distribution::Fixed => self.mean,
+            Distribution::Exponential => -(1.0 - rng.gen::<f64>()).ln() * self.mean,
+            // Box-Muller, with the location shifted for the mean to stay `mean`
+            Distribution::Lognormal => {
+                let z = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * rng.gen::<f64>()).cos();
+                (self.mean.ln() - self.sigma * self.sigma / 2.0 + self.sigma * z).exp()
+            }
+        };
+        Duration::from_secs_f64(micros / 1e6)
     }
-    Ok(g_addr)
 }
 
-async fn run(addr: SocketAddr, size: usize) -> Result<(), Box<dyn std::error::Error>> {
-    let socket = Arc::new(UdpSocket::bind(addr).await?);
+// Service times are waited out, they cannot be negative
+fn parse_service(mean: &str) -> Result<f64, String> {
+    match mean.parse::<f64>() {
+        Ok(micros) if micros >= 0.0 && micros.is_finite() => Ok(micros),
+        Ok(_) => Err(format!("{}: the service time has to be 0 or more", mean)),
+        Err(e) => Err(format!("{:?}: {}", mean, e)),
+    }
+}
+
+// How the sockets wait for data: parked on the runtime's reactor or busy
+// polled, and the SO_BUSY_POLL budget in microseconds, 0 is off.
+#[derive(Clone, Copy, Debug)]
+struct Poll {
+    busy: bool,
+    budget: u32,
+}
+
+// Cores as taskset takes them, 0,2-3. Leaked, for the pinning to stay Copy
+// like the rest of RuntimeConfig.
+fn parse_cpus(cpus: &str) -> Result<&'static [usize], String> {
+    let mut cores = Vec::new();
+    for part in cpus.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_core(first)?, parse_core(last)?);
+                if first > last {
+                    return Err(format!("{}-{} is an empty range", first, last));
+                }
+                cores.extend(first..=last);
+            }
+            None => cores.push(parse_core(part)?),
+        }
+    }
+    Ok(Box::leak(cores.into_boxed_slice()))
+}
+
+// A core the process may run on, taskset may leave it fewer than the host has.
+// Any other one would only fail once a thread gets pinned to it.
+fn parse_core(core: &str) -> Result<usize, String> {
+    let n = core
+        .parse::<usize>()
+        .map_err(|e| format!("{:?}: {}", core, e))?;
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(format!("{}: {}", n, std::io::Error::last_os_error()));
+    }
+    if n >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(n, &set) } {
+        return Err(format!("{}: not one of the cores of the process", n));
+    }
+    Ok(n)
+}
+
+// Pins thread `tid` to `core` and records it on stderr, the mapping of the run
+fn pin_thread(tid: libc::pid_t, name: &str, core: usize) -> std::io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(core, &mut set) };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(tid, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    eprintln!("{} ({}): core {}", name, tid, core);
+    Ok(())
+}
+
+// Pins the calling thread to `core`, the threads it spawns inherit it
+fn pin(core: usize) -> std::io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+    pin_thread(tid, thread::current().name().unwrap_or("unnamed"), core)
+}
+
+// SO_BUSY_POLL: blocking reads and epoll waits on the socket poll the device
+// queue for up to `micros` before going to sleep.
+fn set_busy_poll(socket: &impl AsRawFd, micros: u32) -> std::io::Result<()> {
+    let value = micros as libc::c_int;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_BUSY_POLL,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// CPU time of the whole process, every thread included
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+// The std socket behind an async one, for non-blocking calls on it
+fn borrow_std<T: FromRawFd>(socket: &impl AsRawFd) -> ManuallyDrop<T> {
+    ManuallyDrop::new(unsafe { T::from_raw_fd(socket.as_raw_fd()) })
+}
+
+// Busy polling retries a non-blocking call until it goes through, yielding in
+// between so that the runtime never parks in epoll waiting for the socket.
+// None once `timeout` went by without it going through.
+async fn spin_for<R>(
+    timeout: Duration,
+    mut op: impl FnMut() -> std::io::Result<R>,
+) -> Option<std::io::Result<R>> {
+    let start = Instant::now();
+    loop {
+        match op() {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if start.elapsed() >= timeout {
+                    return None;
+                }
+                task::yield_now().await;
+            }
+            res => return Some(res),
+        }
+    }
+}
+
+async fn process(work: Work, duration: Duration) {
+    match work {
+        Work::None => {}
+        Work::Spin => {
+            let start = Instant::now();
+            while start.elapsed() < duration {
+                std::hint::spin_loop();
+            }
+        }
+        // Stalls whatever else runs on this thread
+        Work::Block => thread::sleep(duration),
+        Work::Offload => task::spawn_blocking(move || thread::sleep(duration))
+            .await
+            .unwrap(),
+        Work::Timer => time::sleep(duration).await,
+    }
+}
+
+// SO_REUSEPORT: sockets bound to the same port share it, the kernel spreads
+// the connections or datagrams among them by a hash of the source.
+fn set_reuse_port(socket: &impl AsRawFd) -> std::io::Result<()> {
+    let value: libc::c_int = 1;
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// The cores the process may run on, as taskset left them
+fn cores() -> std::io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize)
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+        .collect())
+}
+
+// Thread per core: `serve` runs again on a thread pinned to each of `cores`,
+// nothing is shared between them but the port.
+fn per_core(cores: &[usize], serve: impl Fn() + Sync) {
+    thread::scope(|scope| {
+        for &core in cores {
+            let serve = &serve;
+            thread::Builder::new()
+                .name(format!("pong-{}", core))
+                .spawn_scoped(scope, move || {
+                    pin(core).unwrap();
+                    serve();
+                })
+                .unwrap();
+        }
+    });
+}
+
+// IPv6 sockets are dual-stack unless `ipv6_only` is set.
+fn bind(
+    addr: SocketAddr,
+    ipv6_only: bool,
+    reuse_port: bool,
+) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
+    if reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+// All the receivers of a group share its port.
+fn join(
+    addr: SocketAddr,
+    group: IpAddr,
+    interface: Ipv4Addr,
+) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    match group {
+        IpAddr::V4(group) => socket.join_multicast_v4(&group, &interface)?,
+        IpAddr::V6(group) => socket.join_multicast_v6(&group, 0)?,
+    }
+    Ok(socket.into())
+}
+
+// What the pong knows about each of its clients
+struct Peer {
+    datagrams: u64,
+    bytes: u64,
+    since: Instant,
+    last: Instant,
+    cpu: Duration,
+}
+
+fn family(address: SocketAddr) -> &'static str {
+    match address {
+        SocketAddr::V4(_) => "ipv4",
+        SocketAddr::V6(_) => "ipv6",
+    }
+}
+
+fn report(peer: SocketAddr, state: &Peer, size: usize, csv: bool, runtime: RuntimeConfig) {
+    // CPU use of the whole pong since the peer showed up
+    let usage = (cpu_time() - state.cpu).as_secs_f64() / state.since.elapsed().as_secs_f64();
+    if csv {
+        // No load tasks run in the pong, its tasks read like those of an idle ping
+        // framework, transport, test, count, rate, payload, tasks, value, unit, family, peer
+        println!(
+            "{},udp,session,{},0,{},sleep:0,{},bytes,{},{}",
+            runtime,
+            state.datagrams,
+            size,
+            state.bytes,
+            family(peer),
+            peer
+        );
+        println!(
+            "{},udp,cpu,{},0,{},sleep:0,{:.1},%,{},{}",
+            runtime,
+            state.datagrams,
+            size,
+            usage * 100.0,
+            family(peer),
+            peer
+        );
+    } else {
+        println!(
+            "{}: datagrams={} bytes={} active={:?} cpu={:.1}%",
+            peer,
+            state.datagrams,
+            state.bytes,
+            state.last - state.since,
+            usage * 100.0
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run(
+    addr: SocketAddr,
+    size: usize,
+    ipv6_only: bool,
+    reuse_port: bool,
+    idle: Duration,
+    csv: bool,
+    service: Service,
+    poll: Poll,
+    runtime: RuntimeConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = bind(addr, ipv6_only, reuse_port)?;
+    if poll.budget > 0 {
+        set_busy_poll(&socket, poll.budget)?;
+    }
+    let busy = poll
+        .busy
+        .then(|| borrow_std::<std::net::UdpSocket>(&socket));
+    socket.set_nonblocking(true)?;
+    let socket = Arc::new(UdpSocket::from_std(socket)?);
+    let mut buf = vec![0u8; size];
+    let mut peers = HashMap::<SocketAddr, Peer>::new();
+    let mut rng = StdRng::from_entropy();
+    let mut sweep = Instant::now();
+
+    loop {
+        // Wake up at least once per idle period to expire peers
+        let received = match busy.as_deref() {
+            Some(socket) => spin_for(idle, || socket.recv_from(&mut buf)).await,
+            None => time::timeout(idle, socket.recv_from(&mut buf)).await.ok(),
+        };
+        if let Some(res) = received {
+            let (n, peer) = res?;
+            if let Work::None = service.work {
+                // Every datagram goes back to whoever sent it
+                if let Some(socket) = busy.as_deref() {
+                    // A datagram that cannot leave within the idle period is lost
+                    spin_for(idle, || socket.send_to(&buf[..n], peer))
+                        .await
+                        .transpose()?;
+                } else {
+                    socket.send_to(&buf[..n], peer).await?;
+                }
+            } else {
+                // A task per datagram, as the tcp pong has one per connection,
+                // so that the service time of one holds up no other
+                let duration = service.duration(&mut rng);
+                let (socket, datagram) = (socket.clone(), buf[..n].to_vec());
+                task::spawn(async move {
+                    process(service.work, duration).await;
+                    // Lost like any datagram that cannot leave
+                    let _ = socket.send_to(&datagram, peer).await;
+                });
+            }
+            let now = Instant::now();
+            let state = peers.entry(peer).or_insert_with(|| Peer {
+                datagrams: 0,
+                bytes: 0,
+                since: now,
+                last: now,
+                cpu: cpu_time(),
+            });
+            state.datagrams += 1;
+            state.bytes += n as u64;
+            state.last = now;
+        }
+
+        // Peers that went quiet are done, report what they did
+        if sweep.elapsed() >= idle {
+            peers.retain(|peer, state| {
+                let alive = state.last.elapsed() < idle;
+                if !alive {
+                    report(*peer, state, size, csv, runtime);
+                }
+                alive
+            });
+            sweep = Instant::now();
+        }
+    }
+}
+
+async fn run_multicast(
+    addr: SocketAddr,
+    size: usize,
+    group: IpAddr,
+    interface: Ipv4Addr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = join(addr, group, interface)?;
+    socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(socket)?;
+    // Reply from a port of our own so that the ping can tell receivers apart
+    let unspecified: IpAddr = match addr {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let reply = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
 
     let mut buf = vec![0u8; size];
 
     loop {
-        let addr = read_exact(&socket, &mut buf).await?;
-        socket.send_to(&buf, addr).await?;
+        let (n, addr) = socket.recv_from(&mut buf).await?;
+        reply.send_to(&buf[..n], addr).await?;
+    }
+}
+
+// Combinations of arguments clap does not check, refused like a bad value
+fn validate(args: &Args) -> Result<(), clap::Error> {
+    let conflict =
+        |message: &str| Args::command().error(clap::ErrorKind::ArgumentConflict, message);
+    // --per-core runs a current-thread runtime on each core
+    let multi_thread = args.flavor.map_or(!args.per_core, |flavor| {
+        matches!(flavor, Flavor::MultiThread)
+    });
+    if args.per_core && multi_thread {
+        return Err(conflict(
+            "--per-core runs a current-thread runtime on each core, not the multi-thread flavor",
+        ));
     }
+    // The current-thread runtime runs on the main thread
+    if !multi_thread && (args.worker_threads.is_some() || args.disable_lifo_slot) {
+        return Err(conflict(
+            "--worker-threads and --disable-lifo-slot need the multi-thread flavor",
+        ));
+    }
+    if args.disable_lifo_slot && !cfg!(tokio_unstable) {
+        return Err(conflict(
+            "--disable-lifo-slot needs a build with RUSTFLAGS=\"--cfg tokio_unstable\"",
+        ));
+    }
+    if args.reactor_cpu.is_some() {
+        return Err(conflict(
+            "tokio has no reactor thread, its workers take turns on the I/O driver",
+        ));
+    }
+    if args.cpus.is_some() && !multi_thread {
+        return Err(conflict(
+            "--cpus needs the multi-thread flavor, the current-thread runtime runs on the main thread (--main-cpu)",
+        ));
+    }
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    let addr: SocketAddr = env::args()
-        .nth(1)
-        .unwrap()
-        .parse()
-        .expect("First argument must be a valid socket address");
-    let size: usize = env::args()
-        .nth(2)
-        .unwrap()
-        .parse()
-        .expect("Second argument must be the buffer size");
+fn main() {
+    let args = Args::parse();
+    validate(&args).unwrap_or_else(|e| e.exit());
+    let pinning = Pinning {
+        cpus: args.cpus,
+        main: args.main_cpu,
+        reactor: args.reactor_cpu,
+    };
+    // Thread per core: a runtime and a socket on each core taskset left us
+    let cores = args.per_core.then(|| cores().unwrap());
+    let reuse_port = cores.is_some();
+    let runtime = RuntimeConfig {
+        // --per-core runs a current-thread runtime on each core
+        flavor: args.flavor.unwrap_or(if cores.is_some() {
+            Flavor::CurrentThread
+        } else {
+            Flavor::MultiThread
+        }),
+        worker_threads: args.worker_threads,
+        event_interval: args.event_interval,
+        global_queue_interval: args.global_queue_interval,
+        max_blocking_threads: args.max_blocking_threads,
+        lifo_slot: !args.disable_lifo_slot,
+        per_core: cores.as_ref().map(Vec::len),
+        pinning,
+    };
+
+    let service = Service {
+        work: args.work,
+        distribution: args.distribution,
+        mean: args.service,
+        sigma: args.sigma,
+    };
+    let poll = Poll {
+        busy: args.busy_poll,
+        budget: args.so_busy_poll,
+    };
 
-    run(addr, size).await.unwrap();
+    // All of it on this thread, or again on each core with --per-core
+    let serve = || {
+        let rt = runtime.build().unwrap();
+        runtime.pinning.pin_main();
+        rt.block_on(async {
+            match args.join {
+                Some(group) => run_multicast(args.address, args.size, group, args.interface)
+                    .await
+                    .unwrap(),
+                None => run(
+                    args.address,
+                    args.size,
+                    args.ipv6_only,
+                    reuse_port,
+                    Duration::from_secs(args.idle),
+                    args.csv,
+                    service,
+                    poll,
+                    runtime,
+                )
+                .await
+                .unwrap(),
+            }
+        });
+    };
+    match cores {
+        Some(cores) => per_core(&cores, serve),
+        None => serve(),
+    }
 }